@@ -1,5 +1,20 @@
 /// Крейт для вычисления подходящи параметров 
 
+use std::time::Instant;
+use zksnark::{
+    common::seeded_rng,
+    encoding::Encoding,
+    estimator::{estimate, CostModel},
+    params::LweParams,
+    prover::{prove, prove_qap},
+    qap::QuadraticArithmeticProgram as QAP,
+    sap::SquareArithmeticProgram as SAP,
+    setup::{setup, setup_qap},
+    test_ex::{new_test_r1cs, new_test_r1cs_assignment, TEST_FLOODING_BITS},
+};
+
+const PROVER_RUNS: u32 = 20;
+
 fn main() {
     // генерируем простые числа и проверяем условие p, q = 1 mod 2N
     let mut primes = vec![2];
@@ -17,4 +32,35 @@ fn main() {
     }
 
     println!("{:?}", primes);
+
+    // security of the parameter presets, the attacker gets 2n samples
+    for params in [LweParams::toy(), LweParams::bits_80(), LweParams::bits_128()] {
+        println!("{}", estimate(&params, 2 * params.n(), CostModel::Classical));
+    }
+
+    // SAP against QAP on the same R1CS: domain, proof size and prover time
+    let params = LweParams::toy();
+    let (u, w) = new_test_r1cs_assignment();
+    let mut rng = seeded_rng(1);
+    let bytes = |e: &[&Encoding]| e.iter().map(|x| 8 * (x.dimension() + 1)).sum::<usize>();
+
+    let sap = SAP::r1cs_to_sap(new_test_r1cs());
+    let (_, crs, _) = setup(&sap, &params, TEST_FLOODING_BITS, &mut rng).unwrap();
+    let start = Instant::now();
+    for _ in 0..PROVER_RUNS {
+        prove(&crs, u.clone(), w.clone(), &mut rng).unwrap();
+    }
+    let elapsed = start.elapsed() / PROVER_RUNS;
+    let proof = prove(&crs, u.clone(), w.clone(), &mut rng).unwrap();
+    println!("SAP: degree {}, proof {} bytes, prover {:?}", sap.target.degree(), bytes(&[&proof.a, &proof.b]), elapsed);
+
+    let qap = QAP::r1cs_to_qap(new_test_r1cs());
+    let (_, crs, _) = setup_qap(&qap, &params, TEST_FLOODING_BITS, &mut rng).unwrap();
+    let start = Instant::now();
+    for _ in 0..PROVER_RUNS {
+        prove_qap(&crs, u.clone(), w.clone(), &mut rng).unwrap();
+    }
+    let elapsed = start.elapsed() / PROVER_RUNS;
+    let proof = prove_qap(&crs, u, w, &mut rng).unwrap();
+    println!("QAP: degree {}, proof {} bytes, prover {:?}", qap.target.degree(), bytes(&[&proof.a, &proof.b, &proof.c]), elapsed);
 }
\ No newline at end of file
@@ -5,11 +5,15 @@ use lambdaworks_math::field::{
     element::FieldElement,
 };
 
-use rand::Rng;
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 
 //Babybear Prime p = 2^31 - 2^27 + 1 = 0x78000001 = 2013265921
 // for encode message space
 
+pub const P_MODULUS: u64 = 2013265921;
+pub const Q_MODULUS: u64 = 18446744069414584321;
+
 pub type Fp = Babybear31PrimeField;
 pub type FEp = FieldElement::<Fp>;
 
@@ -19,18 +23,75 @@ pub type FEp = FieldElement::<Fp>;
 pub type F = U64GoldilocksPrimeField;
 pub type FE = FieldElement::<F>;
 
-pub fn sample_fr_elem_zp() -> FEp  {
-    let mut rng = rand::thread_rng();
-    // let random_u64: u64 = rng.gen();
-    let random_u64: u64 = rng.gen_range(1..=1000000);
+/// Uniform element of Z_p
+pub fn sample_fr_elem_zp<R: RngCore + CryptoRng>(rng: &mut R) -> FEp  {
+    let random_u64: u64 = rng.gen_range(0..P_MODULUS);
 
     FEp::from(random_u64)
 }
 
-pub fn sample_fr_elem_zq() -> FE {
-    let mut rng = rand::thread_rng();
-    // let random_u64: u64 = rng.gen();
-    let random_u64: u64 = rng.gen_range(1..=1000000);
+/// Uniform element of Z_q
+pub fn sample_fr_elem_zq<R: RngCore + CryptoRng>(rng: &mut R) -> FE {
+    let random_u64: u64 = rng.gen_range(0..Q_MODULUS);
 
     FE::from(random_u64)
-}
\ No newline at end of file
+}
+
+/// Deterministic ChaCha20 generator, the same seed gives the same
+/// setup, encodings and proofs byte for byte.
+pub fn seeded_rng(seed: u64) -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(seed)
+}
+
+/// Lifts x in Z_p to its representative in [0, p) inside Z_q.
+pub fn fep_to_fe(x: &FEp) -> FE {
+    FE::from(x.representative().limbs[0])
+}
+
+/// Maps a signed integer into Z_q, negative values become q - |x|. The sign is
+/// applied without branching, so secret samples can go through it.
+pub fn fe_from_i64(x: i64) -> FE {
+    // all ones when x < 0, then |x| = (x ^ mask) - mask
+    let mask = x >> 63;
+    let abs = FE::from((x ^ mask).wrapping_sub(mask) as u64);
+
+    // abs - 2 * abs when the sign bit is set, abs otherwise
+    abs - FE::from((mask & 1) as u64) * (abs + abs)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_rng_is_reproducible() {
+        let mut rng1 = seeded_rng(42);
+        let mut rng2 = seeded_rng(42);
+
+        for _ in 0..10 {
+            assert_eq!(sample_fr_elem_zp(&mut rng1), sample_fr_elem_zp(&mut rng2));
+            assert_eq!(sample_fr_elem_zq(&mut rng1), sample_fr_elem_zq(&mut rng2));
+        }
+    }
+
+    #[test]
+    fn signed_integers_map_to_q_minus_abs() {
+        for x in [i64::MIN, -(1 << 40), -1, 0, 1, 1 << 40, i64::MAX] {
+            let abs = FE::from(x.unsigned_abs());
+            assert_eq!(fe_from_i64(x), if x < 0 { -abs } else { abs }, "x = {}", x);
+        }
+    }
+
+    #[test]
+    fn samples_cover_the_whole_field() {
+        let mut rng = seeded_rng(7);
+        let large = FE::from(1u64 << 62);
+
+        let above = (0..64)
+            .map(|_| sample_fr_elem_zq(&mut rng))
+            .filter(|x| x.representative() > large.representative())
+            .count();
+        assert!(above > 0);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::{
+    common::FEp,
+    r1cs::{Constraint, CreationError, SparseMatrix, SparseR1CS, R1CS},
+};
+use std::{
+    cell::Cell,
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+    rc::Rc,
+};
+
+/// Variable of a `ConstraintSystem`. The R1CS assignment is laid out as
+/// (1, inputs.., witnesses.., outputs..), outputs count as private variables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Variable {
+    One,
+    Input(usize),
+    Witness(usize),
+    Output(usize),
+}
+
+/// sum_i c_i * x_i over the variables of a constraint system, one term per variable.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinearCombination {
+    terms: Vec<(Variable, FEp)>,
+}
+
+impl LinearCombination {
+    pub fn zero() -> Self {
+        LinearCombination { terms: Vec::new() }
+    }
+
+    /// The constant c, c * 1.
+    pub fn constant(c: FEp) -> Self {
+        LinearCombination::zero().add_term(Variable::One, c)
+    }
+
+    pub fn terms(&self) -> &[(Variable, FEp)] {
+        &self.terms
+    }
+
+    fn add_term(mut self, x: Variable, c: FEp) -> Self {
+        match self.terms.iter_mut().find(|(y, _)| *y == x) {
+            Some((_, d)) => *d = &*d + c,
+            None => self.terms.push((x, c)),
+        }
+        self.terms.retain(|(_, c)| *c != FEp::zero());
+        self
+    }
+}
+
+impl From<Variable> for LinearCombination {
+    fn from(x: Variable) -> Self {
+        LinearCombination::zero().add_term(x, FEp::one())
+    }
+}
+
+impl<T: Into<LinearCombination>> Add<T> for LinearCombination {
+    type Output = LinearCombination;
+
+    fn add(self, other: T) -> LinearCombination {
+        other.into().terms.into_iter().fold(self, |acc, (x, c)| acc.add_term(x, c))
+    }
+}
+
+impl<T: Into<LinearCombination>> Sub<T> for LinearCombination {
+    type Output = LinearCombination;
+
+    fn sub(self, other: T) -> LinearCombination {
+        self + -other.into()
+    }
+}
+
+impl Neg for LinearCombination {
+    type Output = LinearCombination;
+
+    fn neg(self) -> LinearCombination {
+        self * -FEp::one()
+    }
+}
+
+impl Mul<FEp> for LinearCombination {
+    type Output = LinearCombination;
+
+    fn mul(self, k: FEp) -> LinearCombination {
+        let terms = self.terms.into_iter().map(|(x, c)| (x, c * &k)).filter(|(_, c)| *c != FEp::zero()).collect();
+        LinearCombination { terms }
+    }
+}
+
+impl<T: Into<LinearCombination>> Add<T> for Variable {
+    type Output = LinearCombination;
+
+    fn add(self, other: T) -> LinearCombination {
+        LinearCombination::from(self) + other
+    }
+}
+
+impl<T: Into<LinearCombination>> Sub<T> for Variable {
+    type Output = LinearCombination;
+
+    fn sub(self, other: T) -> LinearCombination {
+        LinearCombination::from(self) - other
+    }
+}
+
+impl Mul<FEp> for Variable {
+    type Output = LinearCombination;
+
+    fn mul(self, k: FEp) -> LinearCombination {
+        LinearCombination::from(self) * k
+    }
+}
+
+/// Computes the value of a variable from the variables allocated before it.
+pub type Hint = Rc<dyn Fn(&PartialAssignment) -> FEp>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WitnessError {
+    InputCountMismatch { expected: usize, given: usize },
+    /// A witness or output was allocated without a hint
+    MissingHint(Variable),
+    /// A hint read this variable before its own hint had run
+    ReadBeforeAssigned(Variable),
+    /// The generated assignment fails the constraint with this index
+    Unsatisfied(usize),
+}
+
+/// Values known while the hints run, in allocation order.
+pub struct PartialAssignment<'a> {
+    cs: &'a ConstraintSystem,
+    values: Vec<Option<FEp>>,
+    // first variable read before it was assigned
+    unassigned_read: Cell<Option<Variable>>,
+}
+
+impl PartialAssignment<'_> {
+    /// Value of x. Zero if x is not assigned yet, `generate_assignment` then
+    /// fails with `ReadBeforeAssigned` once the reading hint returns.
+    pub fn get(&self, x: Variable) -> FEp {
+        self.values[self.cs.index(x)].clone().unwrap_or_else(|| {
+            if self.unassigned_read.get().is_none() {
+                self.unassigned_read.set(Some(x));
+            }
+            FEp::zero()
+        })
+    }
+
+    pub fn eval(&self, lc: &LinearCombination) -> FEp {
+        lc.terms().iter().fold(FEp::zero(), |acc, (x, c)| acc + c * self.get(*x))
+    }
+}
+
+/// Builder for an `R1CS`: allocate variables, then `enforce` a * b = c over
+/// linear combinations of them. Variables allocated with a hint can be filled
+/// by `generate_assignment`.
+#[derive(Clone, Default)]
+pub struct ConstraintSystem {
+    num_inputs: usize,
+    num_witnesses: usize,
+    num_outputs: usize,
+    constraints: Vec<(LinearCombination, LinearCombination, LinearCombination)>,
+    hints: Vec<(Variable, Hint)>,
+}
+
+impl fmt::Debug for ConstraintSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConstraintSystem")
+            .field("num_inputs", &self.num_inputs)
+            .field("num_witnesses", &self.num_witnesses)
+            .field("num_outputs", &self.num_outputs)
+            .field("constraints", &self.constraints)
+            .field("hints", &self.hints.iter().map(|(x, _)| x).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ConstraintSystem {
+    pub fn new() -> Self {
+        ConstraintSystem::default()
+    }
+
+    pub fn alloc_input(&mut self) -> Variable {
+        self.num_inputs += 1;
+        Variable::Input(self.num_inputs - 1)
+    }
+
+    pub fn alloc_witness(&mut self) -> Variable {
+        self.num_witnesses += 1;
+        Variable::Witness(self.num_witnesses - 1)
+    }
+
+    pub fn alloc_output(&mut self) -> Variable {
+        self.num_outputs += 1;
+        Variable::Output(self.num_outputs - 1)
+    }
+
+    /// Witness whose value `hint` computes from the variables allocated before it.
+    pub fn alloc_witness_with<F: Fn(&PartialAssignment) -> FEp + 'static>(&mut self, hint: F) -> Variable {
+        let x = self.alloc_witness();
+        self.hints.push((x, Rc::new(hint)));
+        x
+    }
+
+    pub fn alloc_output_with<F: Fn(&PartialAssignment) -> FEp + 'static>(&mut self, hint: F) -> Variable {
+        let x = self.alloc_output();
+        self.hints.push((x, Rc::new(hint)));
+        x
+    }
+
+    pub fn enforce<A, B, C>(&mut self, a: A, b: B, c: C)
+    where
+        A: Into<LinearCombination>,
+        B: Into<LinearCombination>,
+        C: Into<LinearCombination>,
+    {
+        self.constraints.push((a.into(), b.into(), c.into()));
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Number of variables of the R1CS, the constant 1 included.
+    pub fn num_variables(&self) -> usize {
+        1 + self.num_inputs + self.num_witnesses + self.num_outputs
+    }
+
+    /// Position of x in the assignment (1, inputs.., witnesses.., outputs..).
+    pub fn index(&self, x: Variable) -> usize {
+        match x {
+            Variable::One => 0,
+            Variable::Input(i) => 1 + i,
+            Variable::Witness(i) => 1 + self.num_inputs + i,
+            Variable::Output(i) => 1 + self.num_inputs + self.num_witnesses + i,
+        }
+    }
+
+    /// Full assignment (1, inputs.., witnesses.., outputs..) for the given input values,
+    /// the hints run in allocation order. The result is checked against the constraints.
+    pub fn generate_assignment(&self, inputs: &[FEp]) -> Result<Vec<FEp>, WitnessError> {
+        if inputs.len() != self.num_inputs {
+            return Err(WitnessError::InputCountMismatch { expected: self.num_inputs, given: inputs.len() });
+        }
+
+        let mut assignment =
+            PartialAssignment { cs: self, values: vec![None; self.num_variables()], unassigned_read: Cell::new(None) };
+        assignment.values[0] = Some(FEp::one());
+        for (i, x) in inputs.iter().enumerate() {
+            assignment.values[1 + i] = Some(x.clone());
+        }
+        for (x, hint) in &self.hints {
+            let value = hint(&assignment);
+            if let Some(read) = assignment.unassigned_read.take() {
+                let has_hint = self.hints.iter().any(|(y, _)| *y == read);
+                return Err(if has_hint { WitnessError::ReadBeforeAssigned(read) } else { WitnessError::MissingHint(read) });
+            }
+            assignment.values[self.index(*x)] = Some(value);
+        }
+
+        let mut missing = (0..self.num_witnesses).map(Variable::Witness).chain((0..self.num_outputs).map(Variable::Output));
+        if let Some(x) = missing.find(|x| assignment.values[self.index(*x)].is_none()) {
+            return Err(WitnessError::MissingHint(x));
+        }
+        if let Some(i) = self.constraints.iter().position(|(a, b, c)| assignment.eval(a) * assignment.eval(b) != assignment.eval(c)) {
+            return Err(WitnessError::Unsatisfied(i));
+        }
+
+        Ok(assignment.values.into_iter().map(Option::unwrap).collect())
+    }
+
+    /// Splits a full assignment into the public inputs and the private part
+    /// (witnesses then outputs), the `u` and `w` of the prover.
+    pub fn split_assignment(&self, assignment: &[FEp]) -> (Vec<FEp>, Vec<FEp>) {
+        let (u, w) = assignment[1..].split_at(self.num_inputs);
+        (u.to_vec(), w.to_vec())
+    }
+
+    /// Dense R1CS with `number_of_inputs` inputs and `number_of_outputs` outputs.
+    pub fn finish(&self) -> Result<R1CS, CreationError> {
+        let constraints = self
+            .constraints
+            .iter()
+            .map(|(a, b, c)| Constraint::new(self.dense(a), self.dense(b), self.dense(c)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        R1CS::new(constraints, self.num_inputs, self.num_outputs)
+    }
+
+    /// Same system as `finish` with A, B, C in CSR form, without going through dense rows.
+    pub fn finish_sparse(&self) -> Result<SparseR1CS, CreationError> {
+        let matrix = |side: fn(&(LinearCombination, LinearCombination, LinearCombination)) -> &LinearCombination| {
+            let triplets = self
+                .constraints
+                .iter()
+                .enumerate()
+                .flat_map(|(i, lcs)| side(lcs).terms().iter().map(move |(x, c)| (i, self.index(*x), c.clone())))
+                .collect();
+            SparseMatrix::from_triplets(self.num_constraints(), self.num_variables(), triplets)
+        };
+
+        SparseR1CS::new(matrix(|lcs| &lcs.0), matrix(|lcs| &lcs.1), matrix(|lcs| &lcs.2), self.num_inputs, self.num_outputs)
+    }
+
+    fn dense(&self, lc: &LinearCombination) -> Vec<FEp> {
+        let mut row = vec![FEp::zero(); self.num_variables()];
+        for (x, c) in lc.terms() {
+            row[self.index(*x)] = c.clone();
+        }
+        row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_ex::{new_test_r1cs, new_test_r1cs_assignment};
+
+    #[test]
+    fn builds_the_test_circuit() {
+        let mut cs = ConstraintSystem::new();
+        let x: Vec<Variable> = (0..4).map(|_| cs.alloc_input()).collect();
+        let c6 = cs.alloc_output();
+        let c5 = cs.alloc_witness();
+
+        cs.enforce(x[2], x[3], c5);
+        cs.enforce(x[0] + x[1], c5, c6);
+
+        assert_eq!(cs.index(c6), 6);
+        assert_eq!(cs.finish(), Ok(new_test_r1cs()));
+        assert_eq!(cs.finish_sparse(), Ok(SparseR1CS::from(&new_test_r1cs())));
+    }
+
+    #[test]
+    fn linear_combinations_merge_terms() {
+        let mut cs = ConstraintSystem::new();
+        let (x, y) = (cs.alloc_input(), cs.alloc_witness());
+
+        let lc = (x + y) * FEp::from(3) - y + LinearCombination::constant(FEp::from(5)) - x * FEp::from(3);
+        assert_eq!(lc.terms(), &[(y, FEp::from(2)), (Variable::One, FEp::from(5))]);
+        assert_eq!(x - x, LinearCombination::zero());
+    }
+
+    #[test]
+    fn hints_generate_the_test_assignment() {
+        let mut cs = ConstraintSystem::new();
+        let x: Vec<Variable> = (0..4).map(|_| cs.alloc_input()).collect();
+        let (x0, x1, x2, x3) = (x[0], x[1], x[2], x[3]);
+        // c6 reads c5, whose hint runs first since c5 is allocated first
+        let c5 = cs.alloc_witness_with(move |s| s.get(x2) * s.get(x3));
+        let c6 = cs.alloc_output_with(move |s| s.eval(&(x0 + x1)) * s.get(c5));
+
+        cs.enforce(x2, x3, c5);
+        cs.enforce(x0 + x1, c5, c6);
+
+        let (u, w) = new_test_r1cs_assignment();
+        let assignment = cs.generate_assignment(&u).unwrap();
+        assert!(cs.finish().unwrap().verify_solution(&assignment));
+        assert_eq!(cs.split_assignment(&assignment), (u, w));
+    }
+
+    #[test]
+    fn generation_reports_bad_hints() {
+        let mut cs = ConstraintSystem::new();
+        let x = cs.alloc_input();
+        let y = cs.alloc_witness_with(move |s| s.get(x) + FEp::one());
+        cs.enforce(x, x, y);
+
+        assert_eq!(cs.generate_assignment(&[]), Err(WitnessError::InputCountMismatch { expected: 1, given: 0 }));
+        assert_eq!(cs.generate_assignment(&[FEp::from(3)]), Err(WitnessError::Unsatisfied(0)));
+
+        let z = cs.alloc_output();
+        assert_eq!(cs.generate_assignment(&[FEp::from(3)]), Err(WitnessError::MissingHint(z)));
+    }
+
+    #[test]
+    fn hints_reading_unassigned_variables_are_reported() {
+        let mut cs = ConstraintSystem::new();
+        let x = cs.alloc_input();
+        let later = Variable::Witness(1);
+        let y = cs.alloc_witness_with(move |s| s.get(x) * s.get(later));
+        let z = cs.alloc_witness_with(move |s| s.get(x));
+        assert_eq!(later, z);
+        cs.enforce(x, z, y);
+        assert_eq!(cs.generate_assignment(&[FEp::from(3)]), Err(WitnessError::ReadBeforeAssigned(z)));
+
+        // a read of a variable without a hint reports the missing hint
+        let mut cs = ConstraintSystem::new();
+        let x = cs.alloc_input();
+        let free = cs.alloc_witness();
+        let y = cs.alloc_witness_with(move |s| s.get(x) + s.get(free));
+        cs.enforce(x, free, y);
+        assert_eq!(cs.generate_assignment(&[FEp::from(3)]), Err(WitnessError::MissingHint(free)));
+    }
+
+    #[test]
+    fn empty_system_is_rejected() {
+        assert_eq!(ConstraintSystem::new().finish(), Err(CreationError::NoConstraints));
+    }
+}
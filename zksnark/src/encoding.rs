@@ -0,0 +1,197 @@
+use crate::{common::*, lwe::inner_product as fe_inner_product, noise::DecodeError};
+use rand::{CryptoRng, RngCore};
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Secret-key encoding interface shared by the scalar and ring backends.
+pub trait EncodingScheme {
+    type Secret;
+    type Message;
+    type Ciphertext;
+
+    fn key_gen<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Self::Secret;
+
+    fn encode<R: RngCore + CryptoRng>(&self, m: Self::Message, s: &Self::Secret, rng: &mut R) -> Self::Ciphertext;
+
+    /// Decodes a fresh encoding.
+    fn decode(&self, s: &Self::Secret, c: &Self::Ciphertext) -> Result<Self::Message, DecodeError>;
+}
+
+/// LWE encoding (c0, c1) = (-a, <a, s> + p * e + m) of a message m in Z_p.
+/// Encodings under the same secret form a Z_q-module, sums and scalar
+/// multiples decode to the same combination of the messages while the noise allows it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Encoding {
+    pub c0: Vec<FE>,
+    pub c1: FE,
+}
+
+impl Encoding {
+    pub fn new(c0: Vec<FE>, c1: FE) -> Self {
+        Encoding { c0, c1 }
+    }
+
+    /// Noiseless encoding of zero of dimension n, neutral element for addition.
+    pub fn zero(n: usize) -> Self {
+        Encoding::new(vec![FE::from(0); n], FE::from(0))
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.c0.len()
+    }
+
+    /// c1 + <c0, s> = p * e + m
+    pub fn phase(&self, s: &Vec<FE>) -> FE {
+        self.c1 + fe_inner_product(&self.c0, s)
+    }
+
+    pub fn scale(&self, k: &FE) -> Self {
+        Encoding::new(self.c0.iter().map(|x| x * k).collect(), self.c1 * k)
+    }
+}
+
+/// sum_i coeffs[i] * encodings[i], coefficients lifted to their representatives in [0, p).
+/// Extra encodings or coefficients are ignored, an empty sum gives `Encoding::zero(n)`.
+pub fn inner_product(encodings: &[Encoding], coeffs: &[FEp], n: usize) -> Encoding {
+    encodings
+        .iter()
+        .zip(coeffs)
+        .fold(Encoding::zero(n), |acc, (enc, c)| acc + enc * c)
+}
+
+impl Add<&Encoding> for &Encoding {
+    type Output = Encoding;
+
+    fn add(self, other: &Encoding) -> Encoding {
+        assert_eq!(self.dimension(), other.dimension(), "encodings should have the same dimension");
+        Encoding::new(
+            self.c0.iter().zip(&other.c0).map(|(x, y)| x + y).collect(),
+            self.c1 + other.c1,
+        )
+    }
+}
+
+impl Add<Encoding> for Encoding {
+    type Output = Encoding;
+
+    fn add(self, other: Encoding) -> Encoding {
+        &self + &other
+    }
+}
+
+impl Sub<&Encoding> for &Encoding {
+    type Output = Encoding;
+
+    fn sub(self, other: &Encoding) -> Encoding {
+        self + &(-other)
+    }
+}
+
+impl Sub<Encoding> for Encoding {
+    type Output = Encoding;
+
+    fn sub(self, other: Encoding) -> Encoding {
+        &self - &other
+    }
+}
+
+impl Neg for &Encoding {
+    type Output = Encoding;
+
+    fn neg(self) -> Encoding {
+        Encoding::new(self.c0.iter().map(|x| -x).collect(), -self.c1)
+    }
+}
+
+impl Neg for Encoding {
+    type Output = Encoding;
+
+    fn neg(self) -> Encoding {
+        -&self
+    }
+}
+
+impl Mul<&FE> for &Encoding {
+    type Output = Encoding;
+
+    fn mul(self, k: &FE) -> Encoding {
+        self.scale(k)
+    }
+}
+
+impl Mul<FE> for Encoding {
+    type Output = Encoding;
+
+    fn mul(self, k: FE) -> Encoding {
+        self.scale(&k)
+    }
+}
+
+impl Mul<&FEp> for &Encoding {
+    type Output = Encoding;
+
+    fn mul(self, k: &FEp) -> Encoding {
+        self.scale(&fep_to_fe(k))
+    }
+}
+
+impl Mul<FEp> for Encoding {
+    type Output = Encoding;
+
+    fn mul(self, k: FEp) -> Encoding {
+        &self * &k
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lwe::LWE, noise::NoiseBudget, params::LweParams};
+
+    #[test]
+    fn add_sub_neg_decode_to_message_arithmetic() {
+        let mut rng = seeded_rng(21);
+        let lwe = LWE::new(LweParams::toy());
+        let s = lwe.key_gen(&mut rng);
+
+        let (m1, m2) = (FEp::from(1000), FEp::from(234));
+        let e1 = lwe.encode(m1.clone(), &s, &mut rng);
+        let e2 = lwe.encode(m2.clone(), &s, &mut rng);
+        let budget = NoiseBudget::fresh(lwe.params()).scale(2);
+
+        assert_eq!(lwe.decode_with_budget(&s, &(&e1 + &e2), &budget), Ok(m1.clone() + m2.clone()));
+        assert_eq!(lwe.decode_with_budget(&s, &(&e1 - &e2), &budget), Ok(m1.clone() - m2.clone()));
+        assert_eq!(lwe.decode(&s, &-&e2), Ok(-m2));
+    }
+
+    #[test]
+    fn inner_product_decodes_to_weighted_sum() {
+        let mut rng = seeded_rng(22);
+        let lwe = LWE::new(LweParams::toy());
+        let s = lwe.key_gen(&mut rng);
+
+        let messages: Vec<FEp> = (1..=4).map(|i| FEp::from(i * 10)).collect();
+        let coeffs: Vec<FEp> = (1..=4).map(FEp::from).collect();
+        let encodings: Vec<Encoding> = messages.iter().map(|m| lwe.encode(m.clone(), &s, &mut rng)).collect();
+
+        let fresh = NoiseBudget::fresh(lwe.params());
+        let budget = NoiseBudget::linear_combination(&[fresh; 4], &coeffs);
+        let expected = messages
+            .iter()
+            .zip(&coeffs)
+            .fold(FEp::from(0), |acc, (m, c)| acc + m * c);
+
+        let combined = inner_product(&encodings, &coeffs, lwe.params().n());
+        assert_eq!(lwe.decode_with_budget(&s, &combined, &budget), Ok(expected));
+    }
+
+    #[test]
+    fn zero_is_neutral() {
+        let mut rng = seeded_rng(23);
+        let lwe = LWE::new(LweParams::toy());
+        let s = lwe.key_gen(&mut rng);
+        let e = lwe.encode(FEp::from(5), &s, &mut rng);
+
+        assert_eq!(&e + &Encoding::zero(e.dimension()), e);
+        assert_eq!(inner_product(&[], &[], 5), Encoding::zero(5));
+    }
+}
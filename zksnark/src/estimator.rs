@@ -0,0 +1,243 @@
+use crate::params::LweParams;
+use std::f64::consts::{E, PI};
+use std::fmt;
+
+/// Smallest and largest BKZ block sizes considered by the search.
+const MIN_BETA: usize = 40;
+const MAX_BETA: usize = 2000;
+
+/// Cost of one call to an SVP oracle in dimension beta, in bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CostModel {
+    /// 0.292 beta, best known classical sieve
+    Classical,
+    /// 0.265 beta, sieving with Grover speedups
+    Quantum,
+}
+
+impl CostModel {
+    pub fn svp_bits(&self, beta: usize) -> f64 {
+        match self {
+            CostModel::Classical => 0.292 * beta as f64,
+            CostModel::Quantum => 0.265 * beta as f64,
+        }
+    }
+}
+
+/// Cost of a single attack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AttackCost {
+    /// BKZ block size
+    pub beta: usize,
+    /// Number of LWE samples used
+    pub samples: usize,
+    /// Secret coordinates guessed, nonzero only for the hybrid attack
+    pub guessed: usize,
+    /// log2 of the attack cost
+    pub bits: f64,
+}
+
+/// Security of a parameter set against the attacks we account for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SecurityReport {
+    pub params: LweParams,
+    pub cost_model: CostModel,
+    pub primal_usvp: Option<AttackCost>,
+    pub dual: Option<AttackCost>,
+    pub hybrid: Option<AttackCost>,
+}
+
+impl SecurityReport {
+    /// Bit security is the cost of the cheapest attack. An attack that
+    /// needs a block size above MAX_BETA counts as MAX_BETA.
+    pub fn bits(&self) -> f64 {
+        let cap = self.cost_model.svp_bits(MAX_BETA);
+        [self.primal_usvp, self.dual, self.hybrid]
+            .iter()
+            .map(|attack| attack.map_or(cap, |a| a.bits))
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+impl fmt::Display for SecurityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "LWE n = {}, log2 q = {:.1}, sigma = {:.1}, {:?} core-SVP",
+            self.params.n(),
+            (self.params.q() as f64).log2(),
+            self.params.std_dev(),
+            self.cost_model
+        )?;
+        for (name, attack) in [("primal uSVP", self.primal_usvp), ("dual", self.dual), ("hybrid", self.hybrid)] {
+            match attack {
+                Some(a) => writeln!(
+                    f,
+                    "  {:<12} beta = {:>4}, m = {:>5}, guessed = {:>4}: {:.1} bits",
+                    name, a.beta, a.samples, a.guessed, a.bits
+                )?,
+                None => writeln!(f, "  {:<12} beta > {}", name, MAX_BETA)?,
+            }
+        }
+        write!(f, "  security: {:.1} bits", self.bits())
+    }
+}
+
+/// Estimates bit security of `params` for its secret distribution,
+/// with up to `max_samples` LWE samples available to the attacker.
+pub fn estimate(params: &LweParams, max_samples: usize, cost_model: CostModel) -> SecurityReport {
+    let secret = params.secret();
+    estimate_with_secret(params, max_samples, cost_model, secret.entropy_bits(params), secret.std_dev(params))
+}
+
+/// Same as `estimate` for a secret with `secret_bits` of entropy per coordinate
+/// and standard deviation `secret_std`.
+pub fn estimate_with_secret(
+    params: &LweParams,
+    max_samples: usize,
+    cost_model: CostModel,
+    secret_bits: f64,
+    secret_std: f64,
+) -> SecurityReport {
+    let n = params.n();
+    let log_q = (params.q() as f64).log2();
+    let sigma = params.std_dev();
+
+    SecurityReport {
+        params: *params,
+        cost_model,
+        primal_usvp: primal_usvp(n, log_q, sigma, secret_std, max_samples, cost_model),
+        dual: dual(n, log_q, sigma, max_samples, cost_model),
+        hybrid: hybrid(n, log_q, sigma, secret_std, secret_bits, max_samples, cost_model),
+    }
+}
+
+/// Root Hermite factor of BKZ-beta under the geometric series assumption.
+pub fn root_hermite_factor(beta: usize) -> f64 {
+    let b = beta as f64;
+    ((PI * b).powf(1.0 / b) * b / (2.0 * PI * E)).powf(1.0 / (2.0 * (b - 1.0)))
+}
+
+// Primal attack via unique SVP on the Bai-Galbraith embedding of dimension d = m + n + 1.
+// BKZ-beta recovers the short vector when
+//      sqrt(beta) * sigma <= delta^(2 beta - d - 1) * det^(1/d),
+// with det = q^m * (sigma / secret_std)^n after rescaling the secret part.
+fn primal_usvp(
+    n: usize,
+    log_q: f64,
+    sigma: f64,
+    secret_std: f64,
+    max_samples: usize,
+    cost_model: CostModel,
+) -> Option<AttackCost> {
+    if n == 0 {
+        return None;
+    }
+    let scale = (sigma / secret_std).log2().max(0.0);
+    let step = (max_samples / 256).max(1);
+
+    (MIN_BETA..=MAX_BETA).find_map(|beta| {
+        let log_delta = root_hermite_factor(beta).log2();
+        let lhs = (beta as f64).sqrt().log2() + sigma.log2();
+
+        (1..=max_samples).step_by(step).find_map(|m| {
+            let d = (m + n + 1) as f64;
+            let log_det = m as f64 * log_q + n as f64 * scale;
+            let rhs = (2.0 * beta as f64 - d - 1.0) * log_delta + log_det / d;
+
+            (lhs <= rhs).then(|| AttackCost { beta, samples: m, guessed: 0, bits: cost_model.svp_bits(beta) })
+        })
+    })
+}
+
+// Dual attack: a vector of norm l = delta^m * q^(n/m) in the dual lattice
+// distinguishes LWE from uniform with advantage eps = exp(-2 pi^2 (l sigma / q)^2),
+// which needs about 1/eps^2 short vectors. One sieve call returns 2^(0.2075 beta) of them.
+fn dual(n: usize, log_q: f64, sigma: f64, max_samples: usize, cost_model: CostModel) -> Option<AttackCost> {
+    (MIN_BETA..=MAX_BETA)
+        .filter_map(|beta| {
+            let log_delta = root_hermite_factor(beta).log2();
+            let m = ((n as f64 * log_q / log_delta).sqrt() as usize).clamp(1, max_samples.max(1));
+            let log_l = m as f64 * log_delta + n as f64 * log_q / m as f64;
+
+            let x = 2f64.powf(log_l + sigma.log2() - log_q);
+            let log_eps = -2.0 * PI * PI * x * x / 2f64.ln();
+            let repetitions = (-2.0 * log_eps - 0.2075 * beta as f64).max(0.0);
+
+            let bits = cost_model.svp_bits(beta) + repetitions;
+            bits.is_finite().then_some(AttackCost { beta, samples: m, guessed: 0, bits })
+        })
+        .min_by(|a, b| a.bits.total_cmp(&b.bits))
+}
+
+// Primal hybrid: guess k secret coordinates, each costing `secret_bits` bits of
+// exhaustive search (square root with meet-in-the-middle), and run uSVP on the remaining n - k.
+fn hybrid(
+    n: usize,
+    log_q: f64,
+    sigma: f64,
+    secret_std: f64,
+    secret_bits: f64,
+    max_samples: usize,
+    cost_model: CostModel,
+) -> Option<AttackCost> {
+    let step = (n / 64).max(1);
+
+    (0..n)
+        .step_by(step)
+        .filter_map(|k| {
+            let guess_bits = k as f64 * secret_bits / 2.0;
+            primal_usvp(n - k, log_q, sigma, secret_std, max_samples, cost_model).map(|attack| AttackCost {
+                guessed: k,
+                bits: attack.bits.max(guess_bits) + 1.0,
+                ..attack
+            })
+        })
+        .min_by(|a, b| a.bits.total_cmp(&b.bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretDistribution;
+
+    #[test]
+    fn root_hermite_factor_decreases_with_block_size() {
+        assert!(root_hermite_factor(100) > root_hermite_factor(200));
+        assert!((root_hermite_factor(500) - 1.0034).abs() < 0.0005);
+    }
+
+    #[test]
+    fn presets_reach_claimed_security() {
+        let report = estimate(&LweParams::bits_80(), 4096, CostModel::Classical);
+        assert!(report.bits() >= 80.0, "{}", report);
+
+        let report = estimate(&LweParams::bits_128(), 6144, CostModel::Classical);
+        assert!(report.bits() >= 128.0, "{}", report);
+    }
+
+    #[test]
+    fn toy_preset_is_insecure() {
+        let report = estimate(&LweParams::toy(), 64, CostModel::Classical);
+        assert!(report.bits() < 40.0, "{}", report);
+    }
+
+    #[test]
+    fn small_secrets_lower_security() {
+        let uniform = LweParams::bits_80();
+        let ternary = uniform.with_secret(SecretDistribution::Ternary).unwrap();
+        let sparse = uniform.with_secret(SecretDistribution::SparseTernary { weight: 64 }).unwrap();
+
+        let bits = |params: &LweParams| estimate(params, 2048, CostModel::Classical).bits();
+        assert!(bits(&ternary) < bits(&uniform));
+        assert!(bits(&sparse) <= bits(&ternary));
+    }
+
+    #[test]
+    fn quantum_cost_is_lower_than_classical() {
+        let params = LweParams::bits_80();
+        let classical = estimate(&params, 2048, CostModel::Classical);
+        let quantum = estimate(&params, 2048, CostModel::Quantum);
+        assert!(quantum.bits() < classical.bits());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{common::*, encoding::Encoding, noise::NoiseBudget, packed::PackedVector, params::LweParams};
+use rand::{CryptoRng, Rng, RngCore};
+
+/// Bits of every coefficient in the random combination of encodings of zero.
+/// Wider coefficients need fewer encodings for the same entropy but add more noise.
+pub const ZERO_COEFF_BITS: u32 = 8;
+
+/// Number of encodings of zero per secret block needed for a random combination with
+/// coefficients in [0, 2^ZERO_COEFF_BITS) to make c0 2^-lambda close to uniform: the
+/// coefficients should carry (n + 1) * log2 q + 2 lambda bits by the leftover hash lemma.
+/// Every block takes its own combination, so packing does not change the count of
+/// packed encodings.
+pub fn zero_samples(params: &LweParams, lambda: u32) -> usize {
+    let bits = (params.n() + 1) * (params.q() as f64).log2().ceil() as usize + 2 * lambda as usize;
+    bits.div_ceil(ZERO_COEFF_BITS as usize)
+}
+
+/// Bound on the noise of the combination of `count` encodings of zero of noise `entry`.
+pub fn zero_sum_budget(entry: &NoiseBudget, count: usize) -> NoiseBudget {
+    entry.inner_product(count, (1 << ZERO_COEFF_BITS) - 1)
+}
+
+/// Whether every budget, rerandomized with encodings of zero whose combination is
+/// bounded by `zeros` and flooded up to statistical distance 2^-lambda, still decodes.
+pub fn floods_within_budget(budgets: &[NoiseBudget], params: &LweParams, zeros: &NoiseBudget, lambda: u32) -> bool {
+    budgets
+        .iter()
+        .all(|b| b.flood(params, zeros, flooding_bound(b, params, lambda)).is_decodable(params))
+}
+
+/// Flooding error bound F = 2^lambda * (bound / p + 1). An error uniform in [-F, F]
+/// hides any noise e with |p e + m| <= bound up to statistical distance 2^-lambda.
+pub fn flooding_bound(budget: &NoiseBudget, params: &LweParams, lambda: u32) -> u128 {
+    let e = budget.bound() / params.p() as u128 + 1;
+    e.saturating_mul(1u128 << lambda.min(126)).min(u128::MAX / 4)
+}
+
+/// c + (0, p * e) + a random combination of `zeros` with coefficients in
+/// [0, 2^ZERO_COEFF_BITS), where e is uniform in [-flood, flood].
+/// The result encodes the same message, with c0 independent of c and noise that
+/// does not depend on the noise of c.
+pub fn rerandomize<R: RngCore + CryptoRng>(
+    c: &Encoding,
+    zeros: &PackedVector,
+    params: &LweParams,
+    flood: u128,
+    rng: &mut R,
+) -> Encoding {
+    let coeffs: Vec<FEp> = (0..zeros.len()).map(|_| FEp::from(rng.gen_range(0..1u64 << ZERO_COEFF_BITS))).collect();
+    let zero = zeros.inner_product(&coeffs, params.n());
+
+    let e = rng.gen_range(0..=2 * flood) as i128 - flood as i128;
+    let e_abs = FE::from((e.unsigned_abs() % params.q() as u128) as u64);
+    let e = if e < 0 { -e_abs } else { e_abs };
+
+    let mut out = c + &zero;
+    out.c1 += FE::from(params.p()) * e;
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lwe::LWE, packed::{flatten, PackedLwe}};
+
+    fn zeros_and_secret(count: usize) -> (PackedVector, Vec<FE>) {
+        let mut rng = seeded_rng(91);
+        let packed = PackedLwe::new(LweParams::toy(), 2);
+        let s = packed.key_gen(&mut rng);
+        (packed.encode_vector(&vec![FEp::from(0); count], &s, &mut rng), flatten(&s))
+    }
+
+    #[test]
+    fn rerandomized_encoding_decodes_to_same_message() {
+        let params = LweParams::toy();
+        let (zeros, s) = zeros_and_secret(2 * zero_samples(&params, 16));
+        let lwe = LWE::new(LweParams::new(2 * params.n(), params.p(), params.q(), params.alfa()).unwrap());
+        let mut rng = seeded_rng(92);
+
+        let c = lwe.encode(FEp::from(4242), &s, &mut rng);
+        let fresh = NoiseBudget::fresh(&params);
+        let zero_sum = zero_sum_budget(&fresh, zeros.len());
+        assert!(floods_within_budget(&[fresh], &params, &zero_sum, 16));
+        let flood = flooding_bound(&fresh, &params, 16);
+        let budget = fresh.flood(&params, &zero_sum, flood);
+
+        let r1 = rerandomize(&c, &zeros, &params, flood, &mut rng);
+        let r2 = rerandomize(&c, &zeros, &params, flood, &mut rng);
+        assert_ne!(r1, r2);
+        assert_ne!(r1.c0, c.c0);
+        assert_eq!(lwe.decode_with_budget(&s, &r1, &budget), Ok(FEp::from(4242)));
+        assert_eq!(lwe.decode_with_budget(&s, &r2, &budget), Ok(FEp::from(4242)));
+    }
+
+    #[test]
+    fn flooding_bound_grows_with_lambda() {
+        let params = LweParams::toy();
+        let fresh = NoiseBudget::fresh(&params);
+        assert_eq!(flooding_bound(&fresh, &params, 10), 1024 * flooding_bound(&fresh, &params, 0));
+        assert_eq!(flooding_bound(&NoiseBudget::new(u128::MAX), &params, 40), u128::MAX / 4);
+    }
+
+    #[test]
+    fn zero_samples_carry_enough_entropy() {
+        let params = LweParams::toy();
+        let bits = |count: usize| count * ZERO_COEFF_BITS as usize;
+        assert!(bits(zero_samples(&params, 40)) >= 6 * 64 + 80);
+        assert!(bits(zero_samples(&params, 40) - 1) < 6 * 64 + 80);
+        assert!(zero_samples(&params, 40) > zero_samples(&params, 16));
+    }
+
+    #[test]
+    fn flooding_fits_the_budget_up_to_some_lambda() {
+        let params = LweParams::toy();
+        let fresh = NoiseBudget::fresh(&params);
+        let zeros = zero_sum_budget(&fresh, zero_samples(&params, 16));
+
+        assert!(floods_within_budget(&[fresh], &params, &zeros, 16));
+        assert!(!floods_within_budget(&[fresh], &params, &zeros, 40));
+        assert!(!floods_within_budget(&[fresh, fresh.scale(1 << 16)], &params, &zeros, 16));
+        assert!(!floods_within_budget(&[fresh], &params, &zeros.scale(1 << 24), 16));
+        assert!(!floods_within_budget(&[NoiseBudget::max_decodable(&params)], &params, &zeros, 0));
+    }
+}
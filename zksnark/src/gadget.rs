@@ -0,0 +1,110 @@
+use crate::{
+    common::*,
+    encoding::Encoding,
+    noise::NoiseBudget,
+    packed::{PackedEncoding, PackedLwe, PackedVector},
+};
+use rand::{CryptoRng, RngCore};
+
+/// Bits per digit of the gadget decomposition of prover coefficients.
+pub const BASE_BITS: u32 = 4;
+
+/// Encodings of B^j * m_i mod p for every message m_i and digit j, B = 2^BASE_BITS.
+/// A coefficient c in [0, p) is split into base B digits c = sum_j c_j B^j and
+/// c * m is computed as sum_j c_j * enc(B^j m), so the noise grows with
+/// digits * (B - 1) per coefficient instead of with p.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GadgetVector {
+    /// B^j * m_i at position i * digits + j
+    pub powers: PackedVector,
+    digits: usize,
+}
+
+impl GadgetVector {
+    /// Encodes the powers of every message, packed as `lwe` packs them.
+    pub fn encode<R: RngCore + CryptoRng>(lwe: &PackedLwe, m: &[FEp], s: &[Vec<FE>], rng: &mut R) -> Self {
+        let digits = digits(lwe.params().p());
+        let base = FEp::from(1u64 << BASE_BITS);
+        let powers: Vec<FEp> = m
+            .iter()
+            .flat_map(|x| std::iter::successors(Some(x.clone()), |y| Some(y * &base)).take(digits))
+            .collect();
+
+        GadgetVector { powers: lwe.encode_vector(&powers, s, rng), digits }
+    }
+
+    pub fn len(&self) -> usize {
+        self.powers.len() / self.digits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.powers.is_empty()
+    }
+
+    pub fn digits(&self) -> usize {
+        self.digits
+    }
+
+    /// Message i as a scalar encoding under `flatten(S)`.
+    pub fn get(&self, i: usize) -> Encoding {
+        assert!(i < self.len(), "index out of range");
+        self.powers.get(i * self.digits)
+    }
+
+    /// sum_i coeffs[i] * enc(m_i) through the digits of the coefficients, extra
+    /// coefficients are ignored.
+    pub fn inner_product(&self, coeffs: &[FEp], n: usize) -> Encoding {
+        let digits: Vec<FEp> = coeffs.iter().take(self.len()).flat_map(|c| self.decompose(c)).collect();
+        self.powers.inner_product(&digits, n)
+    }
+
+    /// Bound of `inner_product` over `terms` coefficients when every entry has noise `entry`.
+    pub fn inner_product_budget(&self, entry: &NoiseBudget, terms: usize) -> NoiseBudget {
+        entry.inner_product(terms * self.digits, (1u128 << BASE_BITS) - 1)
+    }
+
+    /// Same messages with every packed encoding replaced by `f` of it.
+    pub fn map_encodings<F: FnMut(&PackedEncoding) -> PackedEncoding>(&self, f: F) -> GadgetVector {
+        GadgetVector { powers: self.powers.map_encodings(f), digits: self.digits }
+    }
+
+    // base B digits of the representative of c in [0, p), least significant first
+    fn decompose(&self, c: &FEp) -> Vec<FEp> {
+        let x = c.representative().limbs[0];
+        let mask = (1u64 << BASE_BITS) - 1;
+        (0..self.digits).map(|j| FEp::from((x >> (j as u32 * BASE_BITS)) & mask)).collect()
+    }
+}
+
+/// Number of base B digits of an element of Z_p.
+pub fn digits(p: u64) -> usize {
+    (64 - (p - 1).leading_zeros()).div_ceil(BASE_BITS) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lwe::LWE, packed::flatten, params::LweParams};
+
+    #[test]
+    fn large_coefficients_keep_the_noise_small() {
+        let mut rng = seeded_rng(41);
+        let params = LweParams::toy();
+        let packed = PackedLwe::new(params, 2);
+        let s = packed.key_gen(&mut rng);
+
+        let m: Vec<FEp> = (0..3).map(|i| FEp::from(1000 * i + 7)).collect();
+        let v = GadgetVector::encode(&packed, &m, &s, &mut rng);
+        assert_eq!((v.len(), v.digits()), (3, 8));
+
+        let coeffs = vec![-FEp::from(1), FEp::from(P_MODULUS / 2), FEp::from(12345)];
+        let expected = m.iter().zip(&coeffs).fold(FEp::from(0), |acc, (x, c)| acc + x * c);
+        let budget = v.inner_product_budget(&NoiseBudget::fresh(&params), coeffs.len());
+        assert!(budget.is_decodable(&params));
+
+        let lwe = LWE::new(params);
+        let c = v.inner_product(&coeffs, params.n());
+        assert_eq!(lwe.decode_with_budget(&flatten(&s), &c, &budget), Ok(expected));
+        assert_eq!(lwe.decode(&flatten(&s), &v.get(2)), Ok(m[2].clone()));
+    }
+}
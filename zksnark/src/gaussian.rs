@@ -0,0 +1,330 @@
+use crate::common::*;
+use rand::{CryptoRng, Rng, RngCore};
+
+/// Tail cut of the sampler: values with |x| > TAIL_CUT * sigma are never produced.
+/// The probability mass beyond 12 sigma is below 2^-100.
+pub const TAIL_CUT: f64 = 12.0;
+
+/// Largest sigma for which a cumulative distribution table is built.
+/// Above it the table would be too big and rejection sampling is used instead.
+pub const CDT_MAX_SIGMA: f64 = 256.0;
+
+/// How samplers trade speed for resistance to timing side channels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplingMode {
+    /// Early-exit table lookups and rejection sampling, running time depends on the sample.
+    VariableTime,
+    /// Full table scans with branch-free selection, running time does not depend on the sample.
+    ConstantTime,
+}
+
+/// Centered discrete Gaussian D_{Z, sigma} over the integers,
+/// with probability of x proportional to exp(-x^2 / (2 sigma^2)).
+#[derive(Clone, Debug)]
+pub struct DiscreteGaussian {
+    sigma: f64,
+    tail: u64,
+    mode: SamplingMode,
+    /// cdt[k] = 2^63 * P(|x| <= k), empty when rejection sampling is used
+    cdt: Vec<u64>,
+    /// x = x1 + k * x2 with x1, x2 from the table, used in constant time mode for large sigma
+    convolution_k: i64,
+}
+
+impl DiscreteGaussian {
+    pub fn new(sigma: f64) -> DiscreteGaussian {
+        DiscreteGaussian::new_with_mode(sigma, SamplingMode::VariableTime)
+    }
+
+    pub fn new_with_mode(sigma: f64, mode: SamplingMode) -> DiscreteGaussian {
+        assert!(sigma > 0.0 && sigma.is_finite(), "sigma should be positive and finite");
+
+        let tail = (TAIL_CUT * sigma).ceil() as u64;
+
+        if sigma <= CDT_MAX_SIGMA {
+            let cdt = cumulative_table(sigma, tail);
+            return DiscreteGaussian { sigma, tail, mode, cdt, convolution_k: 0 };
+        }
+
+        match mode {
+            SamplingMode::VariableTime => {
+                DiscreteGaussian { sigma, tail, mode, cdt: Vec::new(), convolution_k: 0 }
+            }
+            SamplingMode::ConstantTime => {
+                // sigma^2 = sigma0^2 * (1 + k^2) with sigma0 <= CDT_MAX_SIGMA
+                let k = (sigma / CDT_MAX_SIGMA).ceil();
+                let sigma0 = sigma / (1.0 + k * k).sqrt();
+                let cdt = cumulative_table(sigma0, (TAIL_CUT * sigma0).ceil() as u64);
+                DiscreteGaussian { sigma, tail, mode, cdt, convolution_k: k as i64 }
+            }
+        }
+    }
+
+    pub fn mode(&self) -> SamplingMode {
+        self.mode
+    }
+
+    pub fn sigma(&self) -> f64 {
+        self.sigma
+    }
+
+    pub fn tail(&self) -> u64 {
+        self.tail
+    }
+
+    /// Samples an integer x from D_{Z, sigma}.
+    pub fn sample<R: RngCore + CryptoRng>(&self, rng: &mut R) -> i64 {
+        match self.mode {
+            SamplingMode::VariableTime if self.cdt.is_empty() => self.sample_rejection(rng),
+            SamplingMode::VariableTime => self.sample_cdt(rng),
+            SamplingMode::ConstantTime if self.convolution_k == 0 => self.sample_cdt_ct(rng),
+            SamplingMode::ConstantTime => {
+                let x1 = self.sample_cdt_ct(rng);
+                let x2 = self.sample_cdt_ct(rng);
+                x1 + self.convolution_k * x2
+            }
+        }
+    }
+
+    /// Samples x from D_{Z, sigma} and maps it into Z_q, negative values become q - |x|.
+    pub fn sample_fe<R: RngCore + CryptoRng>(&self, rng: &mut R) -> FE {
+        fe_from_i64(self.sample(rng))
+    }
+
+    // the table stores the folded distribution of |x|,
+    // the sign is drawn separately for nonzero magnitudes
+    fn sample_cdt<R: Rng>(&self, rng: &mut R) -> i64 {
+        let r: u64 = rng.gen::<u64>() >> 1;
+        let magnitude = self.cdt.iter().position(|&c| r < c).unwrap_or(self.cdt.len() - 1) as i64;
+
+        if rng.gen::<bool>() {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    fn sample_cdt_ct<R: Rng>(&self, rng: &mut R) -> i64 {
+        let r: u64 = rng.gen::<u64>() >> 1;
+        let magnitude = cdt_lookup_ct(&self.cdt, r) as i64;
+        let sign = (rng.gen::<u64>() & 1) as i64;
+
+        // (m ^ -1) + 1 = -m, (m ^ 0) + 0 = m
+        (magnitude ^ -sign) + sign
+    }
+
+    // x uniform in [-tail, tail] is accepted with probability exp(-x^2 / (2 sigma^2))
+    fn sample_rejection<R: Rng>(&self, rng: &mut R) -> i64 {
+        let tail = self.tail as i64;
+        loop {
+            let x = rng.gen_range(-tail..=tail);
+            let rho = gaussian_weight(x as f64, self.sigma);
+            if rng.gen::<f64>() < rho {
+                return x;
+            }
+        }
+    }
+}
+
+/// Number of table entries c with c <= r, which is the index of the first entry above r.
+/// Every entry is read and compared without branches, whatever the value of r.
+/// Entries and r should be below 2^63.
+pub fn cdt_lookup_ct<'a, I>(table: I, r: u64) -> u64
+where
+    I: IntoIterator<Item = &'a u64>,
+{
+    let last = table.into_iter().fold((0u64, 0u64), |(index, len), &c| {
+        // c - r - 1 wraps to a value with the top bit set exactly when c <= r
+        (index + (c.wrapping_sub(r).wrapping_sub(1) >> 63), len + 1)
+    });
+
+    // r is always below the last entry, min keeps the index in range without a branch
+    last.0.min(last.1.saturating_sub(1))
+}
+
+/// Uniform element of Z_q without rejection: 128 random bits reduced by field arithmetic.
+/// The statistical distance from uniform is about q / 2^128.
+pub fn sample_uniform_zq_ct<R: RngCore + CryptoRng>(rng: &mut R) -> FE {
+    let hi = FE::from(rng.gen::<u64>());
+    let lo = FE::from(rng.gen::<u64>());
+    let two_32 = FE::from(1u64 << 32);
+
+    hi * two_32 * two_32 + lo
+}
+
+/// Uniform element of Z_q from the sampler matching `mode`.
+pub fn sample_uniform_zq<R: RngCore + CryptoRng>(mode: SamplingMode, rng: &mut R) -> FE {
+    match mode {
+        SamplingMode::VariableTime => sample_fr_elem_zq(rng),
+        SamplingMode::ConstantTime => sample_uniform_zq_ct(rng),
+    }
+}
+
+/// rho_sigma(x) = exp(-x^2 / (2 sigma^2))
+pub fn gaussian_weight(x: f64, sigma: f64) -> f64 {
+    (-(x * x) / (2.0 * sigma * sigma)).exp()
+}
+
+// cumulative table of |x| for x <- D_{Z, sigma}, scaled to 2^63
+fn cumulative_table(sigma: f64, tail: u64) -> Vec<u64> {
+    // P(|x| = 0) ~ rho(0), P(|x| = k) ~ 2 rho(k)
+    let weights: Vec<f64> = (0..=tail)
+        .map(|k| {
+            let rho = gaussian_weight(k as f64, sigma);
+            if k == 0 { rho } else { 2.0 * rho }
+        })
+        .collect();
+    let total: f64 = weights.iter().sum();
+
+    let scale = (1u64 << 63) as f64;
+    let mut acc = 0.0;
+    let mut cdt: Vec<u64> = weights
+        .iter()
+        .map(|w| {
+            acc += w / total;
+            (acc * scale).min(scale - 1.0) as u64
+        })
+        .collect();
+
+    // the last entry should cover every 63-bit value
+    if let Some(last) = cdt.last_mut() {
+        *last = u64::MAX >> 1;
+    }
+
+    cdt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+
+    // counts the 32-bit words drawn, a constant time sampler draws as many for every sample
+    struct CountingRng {
+        inner: ChaCha20Rng,
+        words: usize,
+    }
+
+    impl RngCore for CountingRng {
+        fn next_u32(&mut self) -> u32 {
+            self.words += 1;
+            self.inner.next_u32()
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.words += 2;
+            self.inner.next_u64()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.words += dest.len().div_ceil(4);
+            self.inner.fill_bytes(dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for CountingRng {}
+
+    fn mean_and_variance(samples: &[i64]) -> (f64, f64) {
+        let n = samples.len() as f64;
+        let mean = samples.iter().map(|&x| x as f64).sum::<f64>() / n;
+        let var = samples.iter().map(|&x| (x as f64 - mean).powi(2)).sum::<f64>() / n;
+        (mean, var)
+    }
+
+    #[test]
+    fn cdt_sampler_is_centered_with_expected_variance() {
+        let mut rng = seeded_rng(1);
+        let sigma = 3.2;
+        let dg = DiscreteGaussian::new(sigma);
+        let samples: Vec<i64> = (0..20000).map(|_| dg.sample(&mut rng)).collect();
+
+        let (mean, var) = mean_and_variance(&samples);
+        assert!(mean.abs() < 0.2, "mean = {}", mean);
+        assert!((var - sigma * sigma).abs() < 0.1 * sigma * sigma, "variance = {}", var);
+        assert!(samples.iter().any(|&x| x < 0));
+        assert!(samples.iter().all(|&x| x.unsigned_abs() <= dg.tail()));
+    }
+
+    #[test]
+    fn rejection_sampler_is_centered_with_expected_variance() {
+        let mut rng = seeded_rng(2);
+        let sigma = 1000.0;
+        let dg = DiscreteGaussian::new(sigma);
+        let samples: Vec<i64> = (0..20000).map(|_| dg.sample(&mut rng)).collect();
+
+        let (mean, var) = mean_and_variance(&samples);
+        assert!(mean.abs() < 0.05 * sigma, "mean = {}", mean);
+        assert!((var - sigma * sigma).abs() < 0.1 * sigma * sigma, "variance = {}", var);
+        assert!(samples.iter().any(|&x| x < 0));
+    }
+
+    #[test]
+    fn constant_time_sampler_is_centered_with_expected_variance() {
+        let mut rng = seeded_rng(3);
+        for sigma in [3.2, 1000.0] {
+            let dg = DiscreteGaussian::new_with_mode(sigma, SamplingMode::ConstantTime);
+            let samples: Vec<i64> = (0..20000).map(|_| dg.sample(&mut rng)).collect();
+
+            let (mean, var) = mean_and_variance(&samples);
+            assert!(mean.abs() < 0.05 * sigma.max(4.0), "mean = {}", mean);
+            assert!((var - sigma * sigma).abs() < 0.1 * sigma * sigma, "variance = {}", var);
+            assert!(samples.iter().any(|&x| x < 0));
+        }
+    }
+
+    #[test]
+    fn constant_time_lookup_matches_early_exit_lookup() {
+        let cdt = cumulative_table(3.2, (TAIL_CUT * 3.2).ceil() as u64);
+        let early_exit = |r: u64| cdt.iter().position(|&c| r < c).unwrap_or(cdt.len() - 1) as u64;
+
+        let mut points = vec![0, u64::MAX >> 1];
+        for &c in &cdt {
+            points.extend([c.saturating_sub(1), c, (c + 1).min(u64::MAX >> 1)]);
+        }
+
+        for r in points {
+            assert_eq!(cdt_lookup_ct(&cdt, r), early_exit(r), "r = {}", r);
+        }
+    }
+
+    #[test]
+    fn constant_time_lookup_reads_whole_table() {
+        let cdt = cumulative_table(3.2, (TAIL_CUT * 3.2).ceil() as u64);
+
+        for r in [0, cdt[0], cdt[cdt.len() / 2], u64::MAX >> 1] {
+            let mut reads = 0;
+            cdt_lookup_ct(cdt.iter().inspect(|_| reads += 1), r);
+            assert_eq!(reads, cdt.len(), "r = {}", r);
+        }
+    }
+
+    #[test]
+    fn constant_time_sample_fe_draws_the_same_randomness_for_every_sample() {
+        for sigma in [3.2, 1000.0] {
+            let dg = DiscreteGaussian::new_with_mode(sigma, SamplingMode::ConstantTime);
+            let mut rng = CountingRng { inner: seeded_rng(4), words: 0 };
+            let mut draws = Vec::new();
+            let mut negatives = 0;
+
+            for _ in 0..1000 {
+                let before = rng.words;
+                let x = dg.sample_fe(&mut rng);
+                draws.push(rng.words - before);
+                negatives += (x.representative() > FE::from(Q_MODULUS / 2).representative()) as usize;
+            }
+            assert!(draws.iter().all(|&d| d == draws[0]), "sigma = {}", sigma);
+            assert!(negatives > 0);
+        }
+    }
+
+    #[test]
+    fn negative_samples_map_to_q_minus_abs() {
+        assert_eq!(fe_from_i64(-3) + FE::from(3), FE::from(0));
+        assert_eq!(fe_from_i64(5), FE::from(5));
+    }
+}
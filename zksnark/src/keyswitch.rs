@@ -0,0 +1,128 @@
+use crate::{
+    common::*,
+    encoding::Encoding,
+    packed::{PackedEncoding, PackedLwe, PackedVector},
+    params::Q_DIGITS,
+};
+use rand::{CryptoRng, RngCore};
+
+/// Key switching key from a k x n secret S to S': for every coordinate i < n and
+/// bit j, a packed encoding under S' of the column (S[0][i], .., S[k-1][i]) * 2^j.
+/// The binary gadget keeps the noise added by a switch at p * n * Q_DIGITS * max_error per slot.
+pub struct KeySwitchKey {
+    parts: Vec<Vec<PackedEncoding>>,
+    slots: usize,
+}
+
+impl KeySwitchKey {
+    pub fn new<R: RngCore + CryptoRng>(lwe: &PackedLwe, from: &[Vec<FE>], to: &[Vec<FE>], rng: &mut R) -> Self {
+        assert_eq!(from.len(), lwe.slots(), "the source secret should have one row per slot");
+
+        let parts = (0..lwe.params().n())
+            .map(|i| {
+                let mut column: Vec<FE> = from.iter().map(|row| row[i]).collect();
+                (0..Q_DIGITS)
+                    .map(|_| {
+                        let enc = lwe.encode_zq(column.clone(), to, rng);
+                        column.iter_mut().for_each(|x| *x = *x + *x);
+                        enc
+                    })
+                    .collect()
+            })
+            .collect();
+
+        KeySwitchKey { parts, slots: lwe.slots() }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Packed encoding under S' with the same phase in every slot up to p times the key noise:
+    /// (0, c1) + sum_(i, j) bit_j(c0_i) * K[i][j].
+    pub fn switch_packed(&self, c: &PackedEncoding) -> PackedEncoding {
+        let mut out = self.decompose(&c.c0);
+        for (x, y) in out.c1.iter_mut().zip(&c.c1) {
+            *x += *y;
+        }
+        out
+    }
+
+    pub fn switch_vector(&self, v: &PackedVector) -> PackedVector {
+        v.map_encodings(|c| self.switch_packed(c))
+    }
+
+    /// Switches a scalar encoding of dimension k * n under `flatten(S)`, such as the
+    /// entries returned by `PackedVector::get`. Block t of c0 goes through the key
+    /// and only slot t of the result is kept.
+    pub fn switch(&self, c: &Encoding) -> Encoding {
+        let n = self.dimension();
+        assert_eq!(c.dimension(), self.slots * n, "encoding should be under the flattened source secret");
+
+        c.c0
+            .chunks(n)
+            .enumerate()
+            .map(|(t, block)| self.decompose(block).extract(t))
+            .fold(Encoding::new(vec![FE::from(0); self.slots * n], c.c1), |acc, e| acc + e)
+    }
+
+    // sum_(i, j) bit_j(c0_i) * K[i][j], whose slot t has phase <c0, S[t]> + p * e
+    fn decompose(&self, c0: &[FE]) -> PackedEncoding {
+        let n = self.dimension();
+        let mut out = PackedEncoding { c0: vec![FE::from(0); n], c1: vec![FE::from(0); self.slots] };
+
+        for (x, parts) in c0.iter().zip(&self.parts) {
+            let bits = x.representative().limbs[0];
+            for (j, part) in parts.iter().enumerate() {
+                // add every digit with weight 0 or 1 so the work does not depend on c0
+                let bit = FE::from((bits >> j) & 1);
+                for (acc, y) in out.c0.iter_mut().zip(&part.c0) {
+                    *acc += bit * y;
+                }
+                for (acc, y) in out.c1.iter_mut().zip(&part.c1) {
+                    *acc += bit * y;
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lwe::LWE, noise::NoiseBudget, packed::flatten, params::LweParams};
+
+    #[test]
+    fn switched_packed_encodings_decode_under_new_secret() {
+        let mut rng = seeded_rng(71);
+        let packed = PackedLwe::new(LweParams::toy(), 3);
+        let (old, new) = (packed.key_gen(&mut rng), packed.key_gen(&mut rng));
+        let ksk = KeySwitchKey::new(&packed, &old, &new, &mut rng);
+
+        let m: Vec<FEp> = (0..7).map(|i| FEp::from(1000 * i + 9)).collect();
+        let v = ksk.switch_vector(&packed.encode_vector(&m, &old, &mut rng));
+
+        let lwe = LWE::new(LweParams::toy());
+        let budget = NoiseBudget::fresh(lwe.params()).key_switch(lwe.params(), 1);
+        for (i, mi) in m.iter().enumerate() {
+            assert_eq!(lwe.decode_with_budget(&flatten(&new), &v.get(i), &budget), Ok(mi.clone()));
+        }
+    }
+
+    #[test]
+    fn switched_scalar_encodings_decode_under_new_secret() {
+        let mut rng = seeded_rng(72);
+        let packed = PackedLwe::new(LweParams::toy(), 4);
+        let (old, new) = (packed.key_gen(&mut rng), packed.key_gen(&mut rng));
+        let ksk = KeySwitchKey::new(&packed, &old, &new, &mut rng);
+
+        let v = packed.encode_vector(&[FEp::from(5), FEp::from(6)], &old, &mut rng);
+        let sum = &v.get(0) + &v.get(1);
+        let switched = ksk.switch(&sum);
+
+        let lwe = LWE::new(LweParams::toy());
+        let budget = NoiseBudget::fresh(lwe.params()).scale(2).key_switch(lwe.params(), 4);
+        assert_eq!(lwe.decode_with_budget(&flatten(&new), &switched, &budget), Ok(FEp::from(11)));
+    }
+}
@@ -1,8 +1,25 @@
 pub mod r1cs;
+pub mod constraint_system;
 pub mod sap;
+pub mod qap;
+pub mod ssp;
 pub mod lwe;
+pub mod encoding;
+pub mod rq;
+pub mod rlwe;
+pub mod packed;
+pub mod gadget;
+pub mod modswitch;
+pub mod keyswitch;
+pub mod flooding;
+pub mod gaussian;
+pub mod params;
+pub mod secret;
+pub mod noise;
+pub mod estimator;
 pub mod setup;
 pub mod prover;
 pub mod verifier;
 pub mod common;
-pub mod test_ex;
\ No newline at end of file
+pub mod test_ex;
+pub mod utils;
\ No newline at end of file
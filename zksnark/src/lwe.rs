@@ -1,80 +1,142 @@
-use crate::common::*;
-use rand::Rng;
+use crate::{common::*, encoding::{Encoding, EncodingScheme}, gaussian::{sample_uniform_zq, DiscreteGaussian, SamplingMode}, noise::*, params::LweParams};
+use rand::{CryptoRng, Rng, RngCore};
 
 
-pub struct PK {
-    pub n: usize,
-    pub p: u64,
-    pub q: u64,
-    pub alfa: f64,
+/// Regev public key (A, b = A s + p e) with one row of A per sample.
+/// Anyone holding it can encode, the encodings decode under s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    pub a: Vec<Vec<FE>>,
+    pub b: Vec<FE>,
+}
+
+impl PublicKey {
+    pub fn samples(&self) -> usize {
+        self.b.len()
+    }
 }
 
 pub struct LWE {
-    pk: PK,
-    std_: f64,
+    pk: LweParams,
+    gaussian: DiscreteGaussian,
 }
 
 impl LWE {
-    pub fn new(n: usize, p: u64, q: u64, alfa: f64) -> LWE {
-        // TODO:
-        // - add check gcd(p, q) = 1
-        let pk = PK {n, p, q, alfa};
-        let std_ = q as f64 * alfa;
+    pub fn new(pk: LweParams) -> LWE {
+        LWE::new_with_mode(pk, SamplingMode::VariableTime)
+    }
 
-        LWE{pk, std_}
+    /// Same as `new`, with the sampling backend used for secrets, `a` vectors and errors.
+    pub fn new_with_mode(pk: LweParams, mode: SamplingMode) -> LWE {
+        let gaussian = DiscreteGaussian::new_with_mode(pk.std_dev(), mode);
+
+        LWE{pk, gaussian}
     }
 
-    pub fn key_gen(&self) ->  Vec<FE> {
-        let s: Vec<FE> = (0..self.pk.n).map(|_| sample_fr_elem_zq()).collect();
+    pub fn params(&self) -> &LweParams {
+        &self.pk
+    }
 
-        s
+    pub fn mode(&self) -> SamplingMode {
+        self.gaussian.mode()
     }
 
-    pub fn encode(&self, m: FEp, s: &Vec<FE>) -> (Vec<FE>, FE)  {
-        let a: Vec<FE> = (0..self.pk.n).map(|_| sample_fr_elem_zq()).collect();
+    /// Standard deviation of the error distribution, q * alfa
+    pub fn std_dev(&self) -> f64 {
+        self.pk.std_dev()
+    }
 
-        // println!("\na vector:\n");
-        for i in &a {
-            let hex_ = i.clone().representative().to_hex();
-            let _z = u64::from_str_radix(&hex_, 16).unwrap();
-            // println!("{}", z);
-            // println!("s = {}", i);
-        }
+    pub fn key_gen<R: RngCore + CryptoRng>(&self, rng: &mut R) ->  Vec<FE> {
+        self.pk.secret().sample(self.pk.n(), &self.gaussian, rng)
+    }
+
+    pub fn encode<R: RngCore + CryptoRng>(&self, m: FEp, s: &Vec<FE>, rng: &mut R) -> Encoding {
+        let a: Vec<FE> = (0..self.pk.n()).map(|_| self.sample_zq(rng)).collect();
+        let e = self.gaussian.sample_fe(rng);
+
+        // a*s + p*e + m
+        let c1 = inner_product(&a, s) + FE::from(self.pk.p()) * e + fep_to_fe(&m);
+        let c0: Vec<FE> = a.iter().map(|x| -x).collect();
+
+        Encoding::new(c0, c1)
+    }
+
+    /// Publishes `samples` rows of (A, b = A s + p e). Encodings are statistically
+    /// hiding once samples >= (n + 1) * log2(q).
+    pub fn public_key_gen<R: RngCore + CryptoRng>(&self, s: &Vec<FE>, samples: usize, rng: &mut R) -> PublicKey {
+        let a: Vec<Vec<FE>> = (0..samples)
+            .map(|_| (0..self.pk.n()).map(|_| self.sample_zq(rng)).collect())
+            .collect();
+        let b = a
+            .iter()
+            .map(|row| inner_product(row, s) + FE::from(self.pk.p()) * self.gaussian.sample_fe(rng))
+            .collect();
+
+        PublicKey { a, b }
+    }
 
-        let e = discrete_gaussian(self.std_);
-        // println!("\nGenerate error vector from normal distribution: e = {}", u64::from_str_radix(&e.clone().representative().to_hex(), 16).unwrap());
+    /// Encodes m with the public key only: for a random subset r of the rows,
+    /// (c0, c1) = (-r A, r b + p e' + m). Noise is bounded by `NoiseBudget::public_key`.
+    pub fn encode_public<R: RngCore + CryptoRng>(&self, m: FEp, pk: &PublicKey, rng: &mut R) -> Encoding {
+        let mut c0 = vec![FE::from(0); self.pk.n()];
+        let mut c1 = FE::from(self.pk.p()) * self.gaussian.sample_fe(rng) + fep_to_fe(&m);
+
+        for (row, b) in pk.a.iter().zip(&pk.b) {
+            // every row is touched, unselected ones with weight 0
+            let r = FE::from(rng.gen::<bool>() as u64);
+            for (c, x) in c0.iter_mut().zip(row) {
+                *c = *c - r * x;
+            }
+            c1 += r * b;
+        }
 
-        let temp =  inner_product(&a, &s);
-        // println!("\na*s = {}", u64::from_str_radix(&temp.clone().representative().to_hex(), 16).unwrap());
+        Encoding::new(c0, c1)
+    }
 
-        let temp1 =  temp + FE::from(self.pk.p) * e;
-        // println!("\na*s + p*e = {}", u64::from_str_radix(&temp1.clone().representative().to_hex(), 16).unwrap());
+    /// Decodes a fresh encoding (c0, c1) = (-a, a*s + p*e + m).
+    pub fn decode(&self, s: &Vec<FE>, c: &Encoding) -> Result<FEp, DecodeError> {
+        self.decode_with_budget(s, c, &NoiseBudget::fresh(&self.pk))
+    }
 
-        let temp2 =  temp1 + FE::from_hex_unchecked(&m.representative().to_hex());
-        // println!("\na*s + p*e + m = {}", u64::from_str_radix(&temp2.clone().representative().to_hex(), 16).unwrap());
+    /// Decodes an encoding whose noise is bounded by `budget`, e.g. a linear
+    /// combination of fresh encodings.
+    pub fn decode_with_budget(&self, s: &Vec<FE>, c: &Encoding, budget: &NoiseBudget) -> Result<FEp, DecodeError> {
+        if !budget.is_decodable(&self.pk) {
+            return Err(DecodeError::BudgetExceeded);
+        }
 
-        let mut neg_c0: Vec<FE> = a.clone();
-        for i in 0..a.len() {
-            neg_c0[i] = -a[i].clone();
+        // c1 + <c0, s> = p*e + m, lifted to (-q/2, q/2]
+        let x = centered(&c.phase(s));
+        if x.unsigned_abs() > budget.bound() {
+            return Err(DecodeError::NoiseOverflow);
         }
 
-        // (c0, c1)
-        (neg_c0, temp2)
+        let m = x.rem_euclid(self.pk.p() as i128) as u64;
+        Ok(FEp::from(m))
     }
 
-    pub fn decode(&self, s: &Vec<FE>, (c0, c1): (&Vec<FE>, &FE)) -> FEp {
+    fn sample_zq<R: RngCore + CryptoRng>(&self, rng: &mut R) -> FE {
+        sample_uniform_zq(self.mode(), rng)
+    }
+
+}
 
-        let temp =  inner_product(&c0, &s);
-        println!("\n-a*s = {}", u64::from_str_radix(&temp.clone().representative().to_hex(), 16).unwrap());
-        
-        let c0_u64 = u64::from_str_radix(&c1.clone().representative().to_hex(), 16).unwrap();
-        let temp_u64 = u64::from_str_radix(&temp.clone().representative().to_hex(), 16).unwrap();
-        let m =   c0_u64 - temp_u64;
-        // println!("\nc1 - a * s  = {}", m);
+impl EncodingScheme for LWE {
+    type Secret = Vec<FE>;
+    type Message = FEp;
+    type Ciphertext = Encoding;
 
-        FEp::from(m)
+    fn key_gen<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Vec<FE> {
+        LWE::key_gen(self, rng)
     }
 
+    fn encode<R: RngCore + CryptoRng>(&self, m: FEp, s: &Vec<FE>, rng: &mut R) -> Encoding {
+        LWE::encode(self, m, s, rng)
+    }
+
+    fn decode(&self, s: &Vec<FE>, c: &Encoding) -> Result<FEp, DecodeError> {
+        LWE::decode(self, s, c)
+    }
 }
 
 pub fn inner_product(v1: &Vec<FE>, v2: &Vec<FE>) -> FE {
@@ -84,9 +146,34 @@ pub fn inner_product(v1: &Vec<FE>, v2: &Vec<FE>) -> FE {
         .fold(FE::from(0), |x, y| x + y)
 }
 
-fn discrete_gaussian(std_: f64) -> FE {
-    let mut rng = rand::thread_rng();
-    let e: FE = FE::from((rng.gen::<f64>() * std_).round() as u64);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_key_encodings_decode_under_secret() {
+        let mut rng = seeded_rng(31);
+        let lwe = LWE::new(LweParams::toy());
+        let s = lwe.key_gen(&mut rng);
+        let pk = lwe.public_key_gen(&s, 6 * 64, &mut rng);
+        let budget = NoiseBudget::public_key(lwe.params(), pk.samples());
+
+        assert!(budget.is_decodable(lwe.params()));
+        for m in [0, 1, 123456, P_MODULUS - 1] {
+            let c = lwe.encode_public(FEp::from(m), &pk, &mut rng);
+            assert_eq!(lwe.decode_with_budget(&s, &c, &budget), Ok(FEp::from(m)));
+        }
+    }
 
-    e
-}
\ No newline at end of file
+    #[test]
+    fn public_and_secret_encodings_combine() {
+        let mut rng = seeded_rng(32);
+        let lwe = LWE::new(LweParams::toy());
+        let s = lwe.key_gen(&mut rng);
+        let pk = lwe.public_key_gen(&s, 64, &mut rng);
+
+        let c = &lwe.encode_public(FEp::from(40), &pk, &mut rng) + &lwe.encode(FEp::from(2), &s, &mut rng);
+        let budget = NoiseBudget::public_key(lwe.params(), pk.samples()).add(&NoiseBudget::fresh(lwe.params()));
+        assert_eq!(lwe.decode_with_budget(&s, &c, &budget), Ok(FEp::from(42)));
+    }
+}
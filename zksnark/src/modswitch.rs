@@ -0,0 +1,267 @@
+use crate::{
+    common::*,
+    encoding::Encoding,
+    noise::*,
+    params::{LweParams, ParamsError},
+    secret::SecretDistribution,
+};
+
+/// Encoding (c0, c1) with entries in Z_q' for a modulus q' below q,
+/// produced from an `Encoding` by `switch_modulus`. Entries are kept as u64,
+/// `to_bytes` stores each of them in `bits()` bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwitchedEncoding {
+    pub modulus: u64,
+    pub c0: Vec<u64>,
+    pub c1: u64,
+}
+
+impl SwitchedEncoding {
+    pub fn dimension(&self) -> usize {
+        self.c0.len()
+    }
+
+    /// ceil(log2 q'), the bits of one entry.
+    pub fn bits(&self) -> u32 {
+        64 - (self.modulus - 1).leading_zeros()
+    }
+
+    /// q' and the dimension as little endian u64 and u32, then c0 followed by c1
+    /// at `bits()` bits per entry, least significant bit first.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.modulus.to_le_bytes().to_vec();
+        bytes.extend((self.dimension() as u32).to_le_bytes());
+
+        let bits = self.bits() as usize;
+        let start = bytes.len();
+        bytes.resize(start + Self::packed_len(self.dimension(), bits), 0);
+        for (i, x) in self.c0.iter().chain(std::iter::once(&self.c1)).enumerate() {
+            for j in 0..bits {
+                let pos = i * bits + j;
+                bytes[start + pos / 8] |= (((x >> j) & 1) as u8) << (pos % 8);
+            }
+        }
+        bytes
+    }
+
+    /// Inverse of `to_bytes`, None if the length does not match the header or
+    /// an entry is not below q'.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match Self::read(bytes)? {
+            (c, []) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Reads one encoding written by `to_bytes` from the front of `bytes`,
+    /// returns it with the remaining bytes.
+    pub fn read(bytes: &[u8]) -> Option<(Self, &[u8])> {
+        let modulus = u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?);
+        let dimension = u32::from_le_bytes(bytes.get(8..12)?.try_into().ok()?) as usize;
+        if modulus < 2 {
+            return None;
+        }
+        let bits = (64 - (modulus - 1).leading_zeros()) as usize;
+        let len = Self::packed_len(dimension, bits);
+        if bytes.len() < 12 + len {
+            return None;
+        }
+        let (data, rest) = bytes[12..].split_at(len);
+
+        let entries: Vec<u64> = (0..=dimension)
+            .map(|i| (0..bits).fold(0u64, |acc, j| {
+                let pos = i * bits + j;
+                acc | (((data[pos / 8] >> (pos % 8)) & 1) as u64) << j
+            }))
+            .collect();
+        if entries.iter().any(|x| *x >= modulus) {
+            return None;
+        }
+
+        let (c1, c0) = entries.split_last()?;
+        Some((SwitchedEncoding { modulus, c0: c0.to_vec(), c1: *c1 }, rest))
+    }
+
+    /// Length of `to_bytes` for this encoding.
+    pub fn byte_len(&self) -> usize {
+        12 + Self::packed_len(self.dimension(), self.bits() as usize)
+    }
+
+    fn packed_len(dimension: usize, bits: usize) -> usize {
+        ((dimension + 1) * bits).div_ceil(8)
+    }
+
+    /// c1 + <c0, s> mod q', the secret lifted to its centered representatives.
+    pub fn phase(&self, s: &[FE]) -> u64 {
+        let q = self.modulus as u128;
+        self.c0
+            .iter()
+            .zip(s)
+            .fold(self.c1 as u128, |acc, (c, x)| {
+                let x = centered(x).rem_euclid(q as i128) as u128;
+                (acc + (*c as u128 * x) % q) % q
+            }) as u64
+    }
+}
+
+/// Largest q' < 2^bits with q' = q mod p, the condition under which switching keeps m mod p.
+pub fn switching_modulus(params: &LweParams, bits: u32) -> Result<u64, ParamsError> {
+    let (p, q) = (params.p() as u128, params.q() as u128);
+    let max = (1u128 << bits.min(64)).min(q) - 1;
+    if max <= 2 * p {
+        return Err(ParamsError::ModulusTooSmall);
+    }
+
+    Ok((max - (max + p - q % p) % p) as u64)
+}
+
+/// Whether q' is a modulus `switch_modulus` can produce: 2p < q' < q and q' = q mod p.
+/// A modulus read from a proof should pass this before the proof is decoded.
+pub fn is_switching_modulus(params: &LweParams, q_prime: u64) -> bool {
+    let (p, q) = (params.p(), params.q());
+    q_prime > 2 * p && q_prime < q && q_prime % p == q % p
+}
+
+/// Rescales every entry c to the integer y = c mod p closest to c * q' / q.
+/// The phase becomes (q' / q) * (p e + m) + sum_i tau_i s_i with |tau_i| <= p / 2 + 1,
+/// still equal to m mod p, see `NoiseBudget::switch_modulus` for the new bound.
+/// The tau_i s_i terms only fit below q' / 2 for short secrets, so a uniform
+/// secret distribution is rejected.
+pub fn switch_modulus(c: &Encoding, params: &LweParams, q_prime: u64) -> Result<SwitchedEncoding, ParamsError> {
+    let (p, q) = (params.p(), params.q());
+    assert_eq!(q_prime % p, q % p, "q' should be congruent to q mod p");
+    if params.secret() == SecretDistribution::Uniform {
+        return Err(ParamsError::UniformSecret);
+    }
+
+    let round = |x: &FE| -> u64 {
+        let x = x.representative().limbs[0] as u128;
+        let base = (x * q_prime as u128 / q as u128) as i128;
+        let mut d = (x as i128 - base).rem_euclid(p as i128);
+        if d > (p / 2) as i128 {
+            d -= p as i128;
+        }
+        (base + d).rem_euclid(q_prime as i128) as u64
+    };
+
+    Ok(SwitchedEncoding { modulus: q_prime, c0: c.c0.iter().map(round).collect(), c1: round(&c.c1) })
+}
+
+/// Decodes a switched encoding whose noise is bounded by `budget`, which should
+/// already account for the switch.
+pub fn decode_switched(
+    params: &LweParams,
+    s: &[FE],
+    c: &SwitchedEncoding,
+    budget: &NoiseBudget,
+) -> Result<FEp, DecodeError> {
+    let q = c.modulus as i128;
+    if budget.bound() >= (q / 2) as u128 {
+        return Err(DecodeError::BudgetExceeded);
+    }
+
+    let mut x = c.phase(s) as i128;
+    if x > q / 2 {
+        x -= q;
+    }
+    if x.unsigned_abs() > budget.bound() {
+        return Err(DecodeError::NoiseOverflow);
+    }
+
+    Ok(FEp::from(x.rem_euclid(params.p() as i128) as u64))
+}
+
+/// sum_i |s_i| over the centered representatives.
+pub fn secret_l1_norm(s: &[FE]) -> u128 {
+    s.iter().map(|x| centered(x).unsigned_abs()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lwe::LWE;
+
+    // switching needs a short secret, the rounding noise grows with ||s||_1
+    fn ternary_params() -> LweParams {
+        LweParams::toy().with_secret(SecretDistribution::Ternary).unwrap()
+    }
+
+    #[test]
+    fn switching_modulus_is_congruent_to_q() {
+        let params = LweParams::toy();
+        let q_prime = switching_modulus(&params, 48).unwrap();
+
+        assert!(q_prime < 1 << 48);
+        assert!(q_prime + params.p() >= 1 << 48);
+        assert_eq!(q_prime % params.p(), params.q() % params.p());
+        assert_eq!(switching_modulus(&params, 20), Err(ParamsError::ModulusTooSmall));
+    }
+
+    #[test]
+    fn switched_encodings_decode_to_the_same_message() {
+        let mut rng = seeded_rng(61);
+        let params = ternary_params();
+        let lwe = LWE::new(params);
+        let s = lwe.key_gen(&mut rng);
+        let q_prime = switching_modulus(&params, 56).unwrap();
+
+        let budget = NoiseBudget::fresh(&params).switch_modulus(&params, q_prime, secret_l1_norm(&s));
+        for m in [0, 77, P_MODULUS - 1] {
+            let c = switch_modulus(&lwe.encode(FEp::from(m), &s, &mut rng), &params, q_prime).unwrap();
+            assert!(c.c1 < q_prime);
+            assert_eq!(decode_switched(&params, &s, &c, &budget), Ok(FEp::from(m)));
+        }
+    }
+
+    #[test]
+    fn switching_commutes_with_linear_combinations() {
+        let mut rng = seeded_rng(62);
+        let params = ternary_params();
+        let lwe = LWE::new(params);
+        let s = lwe.key_gen(&mut rng);
+        let q_prime = switching_modulus(&params, 56).unwrap();
+
+        let c = &(&lwe.encode(FEp::from(10), &s, &mut rng) * &FEp::from(3)) + &lwe.encode(FEp::from(4), &s, &mut rng);
+        let budget = NoiseBudget::fresh(&params)
+            .scale(4)
+            .switch_modulus(&params, q_prime, secret_l1_norm(&s));
+        let switched = switch_modulus(&c, &params, q_prime).unwrap();
+        assert_eq!(decode_switched(&params, &s, &switched, &budget), Ok(FEp::from(34)));
+    }
+
+    #[test]
+    fn rejects_uniform_secrets() {
+        let mut rng = seeded_rng(63);
+        let params = LweParams::toy();
+        let lwe = LWE::new(params);
+        let c = lwe.encode(FEp::from(5), &lwe.key_gen(&mut rng), &mut rng);
+        let q_prime = switching_modulus(&params, 56).unwrap();
+
+        assert_eq!(switch_modulus(&c, &params, q_prime), Err(ParamsError::UniformSecret));
+    }
+
+    #[test]
+    fn serialized_entries_take_log_q_prime_bits() {
+        let mut rng = seeded_rng(64);
+        let params = LweParams::new(256, P_MODULUS, Q_MODULUS, 2f64.powi(-62))
+            .unwrap()
+            .with_secret(SecretDistribution::Ternary)
+            .unwrap();
+        let lwe = LWE::new(params);
+        let s = lwe.key_gen(&mut rng);
+        let q_prime = switching_modulus(&params, 40).unwrap();
+
+        let c = switch_modulus(&lwe.encode(FEp::from(9), &s, &mut rng), &params, q_prime).unwrap();
+        let bytes = c.to_bytes();
+        assert_eq!(c.bits(), 40);
+        assert_eq!(bytes.len(), c.byte_len());
+        assert_eq!(bytes.len(), 12 + 257 * 40 / 8);
+        assert!(bytes.len() < 257 * 8);
+
+        let back = SwitchedEncoding::from_bytes(&bytes).unwrap();
+        assert_eq!(back, c);
+        let budget = NoiseBudget::fresh(&params).switch_modulus(&params, q_prime, secret_l1_norm(&s));
+        assert_eq!(decode_switched(&params, &s, &back, &budget), Ok(FEp::from(9)));
+        assert_eq!(SwitchedEncoding::from_bytes(&bytes[..bytes.len() - 1]), None);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{common::*, params::{LweParams, Q_DIGITS}};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The worst case bound already reaches q / 2, the result cannot be trusted
+    BudgetExceeded,
+    /// c1 + <c0, s> is farther from zero than the noise bound allows
+    NoiseOverflow,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BudgetExceeded => write!(f, "noise bound reaches q / 2"),
+            DecodeError::NoiseOverflow => write!(f, "decoded value exceeds the noise bound"),
+        }
+    }
+}
+
+/// Worst case bound on |p * e + m| for an encoding, where c1 + <c0, s> = p * e + m mod q.
+/// A fresh encoding has |e| <= max_error and 0 <= m < p. Linear combinations of
+/// encodings add bounds and multiply them by the integer coefficients, saturating at u128::MAX.
+/// The encoding decodes correctly while the bound stays below q / 2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoiseBudget {
+    bound: u128,
+}
+
+impl NoiseBudget {
+    pub fn new(bound: u128) -> Self {
+        NoiseBudget { bound }
+    }
+
+    /// Bound of a freshly generated encoding: p * max_error + p - 1
+    pub fn fresh(params: &LweParams) -> Self {
+        let p = params.p() as u128;
+        NoiseBudget::new(p.saturating_mul(params.max_error() as u128).saturating_add(p - 1))
+    }
+
+    /// Bound of an encoding made with a public key of `samples` rows:
+    /// p * (samples + 1) * max_error + p - 1
+    pub fn public_key(params: &LweParams, samples: usize) -> Self {
+        let p = params.p() as u128;
+        let errors = (params.max_error() as u128).saturating_mul(samples as u128 + 1);
+        NoiseBudget::new(p.saturating_mul(errors).saturating_add(p - 1))
+    }
+
+    /// Bound of an encoding with zero noise, such as a public constant.
+    pub fn zero() -> Self {
+        NoiseBudget::new(0)
+    }
+
+    /// Largest bound that still decodes, used when no tighter bound is known.
+    pub fn max_decodable(params: &LweParams) -> Self {
+        NoiseBudget::new((params.q() / 2) as u128 - 1)
+    }
+
+    pub fn bound(&self) -> u128 {
+        self.bound
+    }
+
+    pub fn add(&self, other: &NoiseBudget) -> Self {
+        NoiseBudget::new(self.bound.saturating_add(other.bound))
+    }
+
+    /// Bound after multiplying the encoding by an integer of absolute value at most `c`.
+    pub fn scale(&self, c: u128) -> Self {
+        NoiseBudget::new(self.bound.saturating_mul(c))
+    }
+
+    /// Bound of sum_i coeffs[i] * enc_i, where the coefficients are lifted to
+    /// their representatives in [0, p) as the prover does.
+    pub fn linear_combination(budgets: &[NoiseBudget], coeffs: &[FEp]) -> Self {
+        budgets
+            .iter()
+            .zip(coeffs)
+            .map(|(b, c)| b.scale(c.representative().limbs[0] as u128))
+            .fold(NoiseBudget::zero(), |acc, b| acc.add(&b))
+    }
+
+    /// Bound of a sum of `terms` encodings of bound `self`, each multiplied by
+    /// an integer of absolute value at most `max_coeff`.
+    pub fn inner_product(&self, terms: usize, max_coeff: u128) -> Self {
+        self.scale(max_coeff).scale(terms as u128)
+    }
+
+    /// Bound after switching from q to q' with a secret of l1 norm `secret_l1`:
+    /// bound * q' / q + (p / 2 + 1) * (1 + secret_l1).
+    pub fn switch_modulus(&self, params: &LweParams, q_prime: u64, secret_l1: u128) -> Self {
+        let scaled = self.bound.saturating_mul(q_prime as u128).div_ceil(params.q() as u128);
+        let rounding = (params.p() as u128 / 2 + 1).saturating_mul(secret_l1.saturating_add(1));
+        NoiseBudget::new(scaled.saturating_add(rounding))
+    }
+
+    /// Bound after a key switch, adding p * max_error for every bit of the n
+    /// coordinates of each of the `blocks` blocks that go through the key.
+    pub fn key_switch(&self, params: &LweParams, blocks: usize) -> Self {
+        let terms = (params.n() * Q_DIGITS * blocks) as u128;
+        self.add(&NoiseBudget::new(params.p() as u128 * params.max_error() as u128).scale(terms))
+    }
+
+    /// Bound after `flooding::rerandomize` with encodings of zero whose sum is
+    /// bounded by `zeros` and flooding bound `flood`.
+    pub fn flood(&self, params: &LweParams, zeros: &NoiseBudget, flood: u128) -> Self {
+        self.add(zeros).add(&NoiseBudget::new(params.p() as u128).scale(flood))
+    }
+
+    pub fn is_decodable(&self, params: &LweParams) -> bool {
+        self.bound < (params.q() / 2) as u128
+    }
+
+    /// log2(q / 2) - log2(bound), negative once decoding can fail.
+    pub fn remaining_bits(&self, params: &LweParams) -> f64 {
+        ((params.q() / 2) as f64).log2() - (self.bound.max(1) as f64).log2()
+    }
+}
+
+/// Representative of x in (-q/2, q/2]
+pub fn centered(x: &FE) -> i128 {
+    let value = x.representative().limbs[0] as i128;
+    if value > (Q_MODULUS / 2) as i128 {
+        value - Q_MODULUS as i128
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encoding::Encoding, lwe::LWE};
+
+    #[test]
+    fn fresh_toy_encodings_are_decodable() {
+        let params = LweParams::toy();
+        let fresh = NoiseBudget::fresh(&params);
+
+        assert!(fresh.is_decodable(&params));
+        assert!(fresh.remaining_bits(&params) > 0.0);
+        assert!(NoiseBudget::max_decodable(&params).is_decodable(&params));
+        assert!(!NoiseBudget::max_decodable(&params).add(&NoiseBudget::new(1)).is_decodable(&params));
+    }
+
+    #[test]
+    fn linear_combination_grows_with_coefficients() {
+        let params = LweParams::toy();
+        let fresh = NoiseBudget::fresh(&params);
+        let budgets = vec![fresh; 3];
+
+        let small = NoiseBudget::linear_combination(&budgets, &[FEp::from(1), FEp::from(2), FEp::from(3)]);
+        assert_eq!(small.bound(), 6 * fresh.bound());
+        assert!(small.is_decodable(&params));
+
+        let large = NoiseBudget::linear_combination(&budgets, &[-FEp::from(1), -FEp::from(1), -FEp::from(1)]);
+        assert!(!large.is_decodable(&params));
+    }
+
+    #[test]
+    fn decodes_linear_combination_within_budget() {
+        let mut rng = seeded_rng(11);
+        let lwe = LWE::new(LweParams::toy());
+        let s = lwe.key_gen(&mut rng);
+
+        let (m1, m2) = (FEp::from(1234), FEp::from(98765));
+        let e1 = lwe.encode(m1.clone(), &s, &mut rng);
+        let e2 = lwe.encode(m2.clone(), &s, &mut rng);
+
+        // 3 * enc(m1) + 5 * enc(m2)
+        let c0: Vec<FE> = e1.c0.iter().zip(&e2.c0).map(|(x, y)| FE::from(3) * x + FE::from(5) * y).collect();
+        let c = Encoding::new(c0, FE::from(3) * e1.c1 + FE::from(5) * e2.c1);
+
+        let fresh = NoiseBudget::fresh(lwe.params());
+        let budget = NoiseBudget::linear_combination(&[fresh, fresh], &[FEp::from(3), FEp::from(5)]);
+
+        assert_eq!(lwe.decode_with_budget(&s, &c, &budget), Ok(FEp::from(3) * m1 + FEp::from(5) * m2));
+    }
+
+    #[test]
+    fn reports_noise_overflow() {
+        let mut rng = seeded_rng(12);
+        let lwe = LWE::new(LweParams::toy());
+        let s = lwe.key_gen(&mut rng);
+        let c = lwe.encode(FEp::from(7), &s, &mut rng);
+
+        let shifted = Encoding::new(c.c0.clone(), c.c1 + FE::from(1u64 << 62));
+        assert_eq!(lwe.decode(&s, &shifted), Err(DecodeError::NoiseOverflow));
+
+        let exhausted = NoiseBudget::fresh(lwe.params()).scale(1 << 30);
+        assert_eq!(lwe.decode_with_budget(&s, &c, &exhausted), Err(DecodeError::BudgetExceeded));
+    }
+
+    #[test]
+    fn saturates_instead_of_overflowing() {
+        let huge = NoiseBudget::new(u128::MAX / 2).scale(4).add(&NoiseBudget::new(1));
+        assert_eq!(huge.bound(), u128::MAX);
+    }
+
+    #[test]
+    fn centered_representative() {
+        assert_eq!(centered(&FE::from(5)), 5);
+        assert_eq!(centered(&-FE::from(5)), -5);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    common::*,
+    encoding::{Encoding, EncodingScheme},
+    gaussian::{sample_uniform_zq, DiscreteGaussian, SamplingMode},
+    lwe::inner_product as fe_inner_product,
+    noise::*,
+    params::LweParams,
+};
+use rand::{CryptoRng, RngCore};
+
+/// Packed LWE encoding (c0, c1) = (-a, S a + p e + m) of k messages under a k x n
+/// secret S, one row per slot. The k slots share `a`, so k messages cost n + k
+/// elements of Z_q instead of k * (n + 1).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedEncoding {
+    pub c0: Vec<FE>,
+    pub c1: Vec<FE>,
+}
+
+impl PackedEncoding {
+    pub fn slots(&self) -> usize {
+        self.c1.len()
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.c0.len()
+    }
+
+    /// Slot j as a scalar encoding of dimension k * n under `flatten(S)`:
+    /// c0 is placed in block j and the other blocks are zero.
+    pub fn extract(&self, j: usize) -> Encoding {
+        let n = self.dimension();
+        let mut c0 = vec![FE::from(0); self.slots() * n];
+        c0[j * n..(j + 1) * n].copy_from_slice(&self.c0);
+
+        Encoding::new(c0, self.c1[j])
+    }
+}
+
+/// Messages packed k per encoding, message i sits in slot i % k of encoding i / k.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedVector {
+    pub encodings: Vec<PackedEncoding>,
+    len: usize,
+    slots: usize,
+}
+
+impl PackedVector {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Same messages with every packed encoding replaced by `f` of it.
+    pub fn map_encodings<F: FnMut(&PackedEncoding) -> PackedEncoding>(&self, f: F) -> PackedVector {
+        PackedVector { encodings: self.encodings.iter().map(f).collect(), len: self.len, slots: self.slots }
+    }
+
+    /// Message i as a scalar encoding under `flatten(S)`.
+    pub fn get(&self, i: usize) -> Encoding {
+        assert!(i < self.len, "index out of range");
+        self.encodings[i / self.slots].extract(i % self.slots)
+    }
+
+    /// sum_i coeffs[i] * enc_i as a scalar encoding under `flatten(S)`, coefficients
+    /// lifted to [0, p) as in `encoding::inner_product`. Accumulates block by block,
+    /// so the cost is that of an unpacked inner product. Extra coefficients are ignored.
+    pub fn inner_product(&self, coeffs: &[FEp], n: usize) -> Encoding {
+        let mut c0 = vec![FE::from(0); self.slots * n];
+        let mut c1 = FE::from(0);
+
+        for (i, c) in coeffs.iter().take(self.len).enumerate() {
+            let (enc, j) = (&self.encodings[i / self.slots], i % self.slots);
+            let c = fep_to_fe(c);
+            for (acc, x) in c0[j * n..(j + 1) * n].iter_mut().zip(&enc.c0) {
+                *acc += c * x;
+            }
+            c1 += c * enc.c1[j];
+        }
+
+        Encoding::new(c0, c1)
+    }
+}
+
+/// Concatenation of the rows of S, the secret under which extracted slots decode.
+pub fn flatten(s: &[Vec<FE>]) -> Vec<FE> {
+    s.concat()
+}
+
+pub struct PackedLwe {
+    pk: LweParams,
+    slots: usize,
+    gaussian: DiscreteGaussian,
+}
+
+impl PackedLwe {
+    pub fn new(pk: LweParams, slots: usize) -> PackedLwe {
+        PackedLwe::new_with_mode(pk, slots, SamplingMode::VariableTime)
+    }
+
+    pub fn new_with_mode(pk: LweParams, slots: usize, mode: SamplingMode) -> PackedLwe {
+        assert!(slots > 0, "at least one slot is needed");
+        let gaussian = DiscreteGaussian::new_with_mode(pk.std_dev(), mode);
+
+        PackedLwe { pk, slots, gaussian }
+    }
+
+    pub fn params(&self) -> &LweParams {
+        &self.pk
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    pub fn mode(&self) -> SamplingMode {
+        self.gaussian.mode()
+    }
+
+    /// k x n secret matrix S, rows drawn from the secret distribution of the parameters
+    pub fn key_gen<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Vec<Vec<FE>> {
+        (0..self.slots).map(|_| self.pk.secret().sample(self.pk.n(), &self.gaussian, rng)).collect()
+    }
+
+    /// Encodes up to k messages with one shared `a`, missing slots encode 0.
+    pub fn encode<R: RngCore + CryptoRng>(&self, m: Vec<FEp>, s: &[Vec<FE>], rng: &mut R) -> PackedEncoding {
+        self.encode_zq(m.iter().map(fep_to_fe).collect(), s, rng)
+    }
+
+    /// Same as `encode` for messages in Z_q, used for key material such as key
+    /// switching keys. Only the phase p e + m is meaningful, not m mod p.
+    pub fn encode_zq<R: RngCore + CryptoRng>(&self, m: Vec<FE>, s: &[Vec<FE>], rng: &mut R) -> PackedEncoding {
+        assert!(m.len() <= self.slots, "at most k messages fit in one packed encoding");
+
+        let a = self.sample_vector(rng);
+        let p = FE::from(self.pk.p());
+
+        // S a + p e + m
+        let c1 = s
+            .iter()
+            .enumerate()
+            .map(|(j, row)| {
+                fe_inner_product(&a, row) + p * self.gaussian.sample_fe(rng) + m.get(j).copied().unwrap_or(FE::from(0))
+            })
+            .collect();
+        let c0 = a.iter().map(|x| -x).collect();
+
+        PackedEncoding { c0, c1 }
+    }
+
+    /// Encodes any number of messages into ceil(len / k) packed encodings.
+    pub fn encode_vector<R: RngCore + CryptoRng>(&self, m: &[FEp], s: &[Vec<FE>], rng: &mut R) -> PackedVector {
+        let encodings = m.chunks(self.slots).map(|chunk| self.encode(chunk.to_vec(), s, rng)).collect();
+
+        PackedVector { encodings, len: m.len(), slots: self.slots }
+    }
+
+    /// Decodes a fresh encoding into its k slots.
+    pub fn decode(&self, s: &[Vec<FE>], c: &PackedEncoding) -> Result<Vec<FEp>, DecodeError> {
+        let budget = NoiseBudget::fresh(&self.pk);
+
+        c.c1
+            .iter()
+            .zip(s)
+            .map(|(c1, row)| {
+                let x = centered(&(*c1 + fe_inner_product(&c.c0, row)));
+                if x.unsigned_abs() > budget.bound() {
+                    return Err(DecodeError::NoiseOverflow);
+                }
+                Ok(FEp::from(x.rem_euclid(self.pk.p() as i128) as u64))
+            })
+            .collect()
+    }
+
+    fn sample_vector<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Vec<FE> {
+        (0..self.pk.n()).map(|_| sample_uniform_zq(self.mode(), rng)).collect()
+    }
+}
+
+impl EncodingScheme for PackedLwe {
+    type Secret = Vec<Vec<FE>>;
+    type Message = Vec<FEp>;
+    type Ciphertext = PackedEncoding;
+
+    fn key_gen<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Vec<Vec<FE>> {
+        PackedLwe::key_gen(self, rng)
+    }
+
+    fn encode<R: RngCore + CryptoRng>(&self, m: Vec<FEp>, s: &Vec<Vec<FE>>, rng: &mut R) -> PackedEncoding {
+        PackedLwe::encode(self, m, s, rng)
+    }
+
+    fn decode(&self, s: &Vec<Vec<FE>>, c: &PackedEncoding) -> Result<Vec<FEp>, DecodeError> {
+        PackedLwe::decode(self, s, c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encoding::inner_product, lwe::LWE};
+
+    #[test]
+    fn encode_decode_all_slots() {
+        let mut rng = seeded_rng(51);
+        let packed = PackedLwe::new(LweParams::toy(), 4);
+        let s = packed.key_gen(&mut rng);
+
+        let m = vec![FEp::from(1), FEp::from(22), FEp::from(333)];
+        let c = packed.encode(m.clone(), &s, &mut rng);
+        assert_eq!(c.dimension() + c.slots(), 5 + 4);
+        assert_eq!(packed.decode(&s, &c), Ok([m, vec![FEp::from(0)]].concat()));
+    }
+
+    #[test]
+    fn extracted_slots_decode_under_flattened_secret() {
+        let mut rng = seeded_rng(52);
+        let packed = PackedLwe::new(LweParams::toy(), 3);
+        let s = packed.key_gen(&mut rng);
+        let lwe = LWE::new(LweParams::toy());
+
+        let m: Vec<FEp> = (0..7).map(|i| FEp::from(10 * i + 1)).collect();
+        let v = packed.encode_vector(&m, &s, &mut rng);
+        assert_eq!((v.len(), v.encodings.len()), (7, 3));
+
+        for (i, mi) in m.iter().enumerate() {
+            assert_eq!(lwe.decode(&flatten(&s), &v.get(i)), Ok(mi.clone()));
+        }
+    }
+
+    #[test]
+    fn inner_product_matches_extracted_sum() {
+        let mut rng = seeded_rng(53);
+        let packed = PackedLwe::new(LweParams::toy(), 4);
+        let s = packed.key_gen(&mut rng);
+        let n = LweParams::toy().n();
+
+        let m: Vec<FEp> = (0..10).map(|i| FEp::from(i + 1)).collect();
+        let coeffs: Vec<FEp> = (0..10).map(|i| FEp::from(2 * i + 3)).collect();
+        let v = packed.encode_vector(&m, &s, &mut rng);
+
+        let extracted: Vec<Encoding> = (0..v.len()).map(|i| v.get(i)).collect();
+        assert_eq!(v.inner_product(&coeffs, n), inner_product(&extracted, &coeffs, 4 * n));
+    }
+}
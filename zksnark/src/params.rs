@@ -0,0 +1,167 @@
+use crate::{common::*, gaussian::TAIL_CUT, secret::SecretDistribution};
+
+/// Number of binary digits of an element of Z_q, the gadget length of key switching keys.
+pub const Q_DIGITS: usize = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParamsError {
+    /// n should be positive
+    ZeroDimension,
+    /// alfa should lie in (0, 1)
+    InvalidNoiseRate,
+    /// q should be greater than p
+    ModulusTooSmall,
+    /// gcd(p, q) should be 1
+    NotCoprime,
+    /// p and q should be the moduli of FEp and FE
+    FieldMismatch,
+    /// p * (|e| + 1) should stay below q / 2, otherwise encodings do not decode
+    NoiseTooLarge,
+    /// the ring backend needs n a power of two with X^n + 1 splitting mod q
+    InvalidRingDimension,
+    /// a sparse secret needs 0 < weight <= n
+    InvalidSecretWeight,
+    /// modulus switching needs a short secret, a uniform one makes the rounding noise too large
+    UniformSecret,
+}
+
+/// LWE parameter set: dimension n, message modulus p, encoding modulus q
+/// and noise rate alfa, the error has standard deviation q * alfa. The secret is
+/// uniform mod q unless another distribution is chosen with `with_secret`.
+/// Only constructed through `new` or the presets, so every instance is valid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LweParams {
+    n: usize,
+    p: u64,
+    q: u64,
+    alfa: f64,
+    secret: SecretDistribution,
+}
+
+impl LweParams {
+    pub fn new(n: usize, p: u64, q: u64, alfa: f64) -> Result<Self, ParamsError> {
+        let params = LweParams { n, p, q, alfa, secret: SecretDistribution::Uniform };
+
+        if n == 0 {
+            Err(ParamsError::ZeroDimension)
+        } else if !(alfa > 0.0 && alfa < 1.0) {
+            Err(ParamsError::InvalidNoiseRate)
+        } else if q <= p {
+            Err(ParamsError::ModulusTooSmall)
+        } else if gcd(p, q) != 1 {
+            Err(ParamsError::NotCoprime)
+        } else if p != P_MODULUS || q != Q_MODULUS {
+            Err(ParamsError::FieldMismatch)
+        } else if (p as f64) * (params.max_error() as f64 + 1.0) >= (q as f64) / 2.0 {
+            Err(ParamsError::NoiseTooLarge)
+        } else {
+            Ok(params)
+        }
+    }
+
+    /// Tiny dimension for tests and examples, offers no security, sigma = 4.
+    pub fn toy() -> Self {
+        LweParams::new(5, P_MODULUS, Q_MODULUS, 2f64.powi(-62)).unwrap()
+    }
+
+    /// About 80 bits of security, n = 2048 and sigma = 4. A fresh encoding takes about
+    /// 37 of the 63 bits below q / 2, the rest is left for the proof and its flooding.
+    pub fn bits_80() -> Self {
+        LweParams::new(2048, P_MODULUS, Q_MODULUS, 2f64.powi(-62)).unwrap()
+    }
+
+    /// About 128 bits of security, n = 3072 and sigma = 1, a fresh encoding takes
+    /// about 35 of the 63 bits below q / 2.
+    pub fn bits_128() -> Self {
+        LweParams::new(3072, P_MODULUS, Q_MODULUS, 2f64.powi(-64)).unwrap()
+    }
+
+    /// Same parameters with secrets drawn from `secret`.
+    pub fn with_secret(self, secret: SecretDistribution) -> Result<Self, ParamsError> {
+        match secret {
+            SecretDistribution::SparseTernary { weight } if weight == 0 || weight > self.n => {
+                Err(ParamsError::InvalidSecretWeight)
+            }
+            _ => Ok(LweParams { secret, ..self }),
+        }
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    pub fn p(&self) -> u64 {
+        self.p
+    }
+
+    pub fn q(&self) -> u64 {
+        self.q
+    }
+
+    pub fn alfa(&self) -> f64 {
+        self.alfa
+    }
+
+    pub fn secret(&self) -> SecretDistribution {
+        self.secret
+    }
+
+    /// Standard deviation of the error distribution, q * alfa
+    pub fn std_dev(&self) -> f64 {
+        self.q as f64 * self.alfa
+    }
+
+    /// Largest |e| the sampler can produce.
+    pub fn max_error(&self) -> u64 {
+        (TAIL_CUT * self.std_dev()).ceil() as u64
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for params in [LweParams::toy(), LweParams::bits_80(), LweParams::bits_128()] {
+            assert_eq!(
+                LweParams::new(params.n(), params.p(), params.q(), params.alfa()),
+                Ok(params)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        assert_eq!(LweParams::new(0, P_MODULUS, Q_MODULUS, 1e-15), Err(ParamsError::ZeroDimension));
+        assert_eq!(LweParams::new(5, P_MODULUS, Q_MODULUS, 0.0), Err(ParamsError::InvalidNoiseRate));
+        assert_eq!(LweParams::new(5, Q_MODULUS, P_MODULUS, 1e-15), Err(ParamsError::ModulusTooSmall));
+        assert_eq!(LweParams::new(5, 6, 218, 1e-15), Err(ParamsError::NotCoprime));
+        assert_eq!(LweParams::new(5, 7, 218, 0.29), Err(ParamsError::FieldMismatch));
+        assert_eq!(LweParams::new(5, P_MODULUS, Q_MODULUS, 0.01), Err(ParamsError::NoiseTooLarge));
+    }
+
+    #[test]
+    fn selects_secret_distribution() {
+        let params = LweParams::toy().with_secret(SecretDistribution::Ternary).unwrap();
+        assert_eq!(params.secret(), SecretDistribution::Ternary);
+        assert_eq!(LweParams::toy().secret(), SecretDistribution::Uniform);
+        assert_eq!(
+            LweParams::toy().with_secret(SecretDistribution::SparseTernary { weight: 6 }),
+            Err(ParamsError::InvalidSecretWeight)
+        );
+    }
+
+    #[test]
+    fn gcd_of_small_numbers() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(7, 218), 1);
+    }
+}
@@ -1,88 +1,278 @@
-use crate::{common::*, lwe::*, sap::SquareArithmeticProgram as SAP, setup::CommonReferenceString};
+use crate::{common::*, encoding::Encoding, flooding::{flooding_bound, rerandomize, zero_sum_budget}, modswitch::{switch_modulus, SwitchedEncoding}, params::{LweParams, ParamsError}, noise::NoiseBudget, sap::AssignmentError, setup::{CommonReferenceString, QapCommonReferenceString, SspCommonReferenceString}};
 use lambdaworks_math::polynomial::Polynomial;
+use rand::{CryptoRng, RngCore};
 
 pub struct Proof {
-    pub v: FE,
+    /// delta * (u(s) + r * t(s))
+    pub a: Encoding,
+    /// f(w) + g(r) + delta * t(s) * h(s)
+    pub b: Encoding,
 }
 
-pub fn prove(crs: CommonReferenceString, u: Vec<FEp>, w: Vec<FEp>) -> (FE, FE) {
-    let full_instance: Vec<FEp> = vec![u.clone(), w.clone()].concat();
-
-    let u_x: Polynomial<FEp> = crs.sap.u_polynomials[0].clone()
-            + crs.sap.u_polynomials[1..]
-                .iter()
-                .zip(full_instance.clone())
-                .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-                .reduce(|x, y| x + y)
-                .unwrap();
-
-    let w_x: Polynomial<FEp> = crs.sap.w_polynomials[0].clone()
-        + crs.sap.w_polynomials[1..]
-            .iter()
-            .zip(full_instance.clone())
-            .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-            .reduce(|x, y| x + y)
-            .unwrap();
-
-    let mid = w.len();
-    let u_x_mid: Polynomial<FEp> = crs.sap.u_polynomials[mid..]
-                .iter()
-                .zip(u.clone())
-                .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-                .reduce(|x, y| x + y)
-                .unwrap();
-
-    let w_x_mid: Polynomial<FEp> = crs.sap.w_polynomials[mid..]
-            .iter()
-            .zip(u.clone())
-            .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-            .reduce(|x, y| x + y)
-            .unwrap();
-    
-    let h_polinomial_from_sap = crs.sap.h_polinomial(&full_instance);
-
-    // calculate f(w)
-    let mut f_w: FE = FE::from(0);
-    for i in 0..w.clone().len() {
-        f_w += FE::from_hex_unchecked(&w[i].representative().to_hex()) * crs.delta_wi_beta_vi[i]
+impl Proof {
+    /// Rescales both elements to the smaller modulus q', see `modswitch::switch_modulus`.
+    pub fn switch_modulus(&self, params: &LweParams, q_prime: u64) -> Result<SwitchedProof, ParamsError> {
+        Ok(SwitchedProof { a: switch_modulus(&self.a, params, q_prime)?, b: switch_modulus(&self.b, params, q_prime)? })
     }
-    
+}
 
-    // calculate g(r)
-    let r = FE::from_hex_unchecked(&sample_fr_elem_zp().representative().to_hex());
-    let u_x_coeff = u_x.coefficients();
-    let mut part_twp_in_g_w: FE = FE::from(0);
+/// Proof with entries mod q' < q, smaller to transmit.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SwitchedProof {
+    pub a: SwitchedEncoding,
+    pub b: SwitchedEncoding,
+}
 
-    for i in 0..u_x_coeff.len() {
-        part_twp_in_g_w += crs.delta_si_t_sk[i] * FE::from_hex_unchecked(&u_x_coeff[i].representative().to_hex());
+impl SwitchedProof {
+    /// `a.to_bytes()` followed by `b.to_bytes()`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.a.to_bytes(), self.b.to_bytes()].concat()
     }
-    
-    let g_r = (r.clone() * r.clone() * crs.delta_t_s_2) + 
-    FE::from(2) * r.clone() * part_twp_in_g_w + r * crs.beta_t_sk;
 
-
-    // calculate A proof
-    let mut delta_v_s: FE = FE::from(0);
-    for i in 0..u_x_coeff.len() {
-        delta_v_s += crs.delta_si[i] * FE::from_hex_unchecked(&u_x_coeff[i].representative().to_hex());
+    /// Inverse of `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (a, rest) = SwitchedEncoding::read(bytes)?;
+        Some(SwitchedProof { a, b: SwitchedEncoding::from_bytes(rest)? })
     }
+}
 
-    let t_x_coeff = crs.sap.target.coefficients();
-    let mut delta_t_s: FE = FE::from(0);
-    for i in 0..t_x_coeff.len() {
-        delta_t_s += crs.delta_si[i] * FE::from_hex_unchecked(&t_x_coeff[i].representative().to_hex());
+/// Proof for public input `u` and R1CS witness `w`, the extra SAP variables are
+/// computed with `extend_witness`. Fails when the assignment has the wrong length
+/// or does not satisfy the SAP.
+pub fn prove<R: RngCore + CryptoRng>(
+    crs: &CommonReferenceString,
+    u: Vec<FEp>,
+    w: Vec<FEp>,
+    rng: &mut R,
+) -> Result<Proof, AssignmentError> {
+    let n = crs.pk.n();
+    let inputs = crs.sap.num_instance_variables - 1;
+    if u.len() != inputs {
+        return Err(AssignmentError::WrongLength { expected: inputs, actual: u.len() });
     }
+    let w = crs.sap.extend_witness(&[vec![FEp::from(1)], u.clone(), w].concat())?;
+    let full_instance: Vec<FEp> = vec![u.clone(), w.clone()].concat();
+    crs.sap.is_satisfied(&full_instance)?;
+
+    let u_x = crs.sap.u_polinomial(&full_instance);
+
+    let h_polinomial_from_sap = crs.sap.h_polinomial(&full_instance)?;
+
+    // every encoding is scaled once, the field coefficients are combined first
+    let r = sample_fr_elem_zp(rng);
+    let coefficient = |p: &Polynomial<FEp>, i: usize| p.coefficients().get(i).cloned().unwrap_or(FEp::from(0));
+
+    // calculate A proof = delta * (u(s) + r t(s))
+    let a_coeffs: Vec<FEp> = (0..crs.delta_si.len())
+        .map(|i| coefficient(&u_x, i) + &r * coefficient(&crs.sap.target, i))
+        .collect();
+    let a = crs.delta_si.inner_product(&a_coeffs, n);
+
+    // calculate B proof = f(w) + g(r) + delta * t(s) * h(s), where
+    // g(r) = r^2 delta t(s)^2 + 2 r delta t(s) u(s) + r beta t(s)
+    let f_w = crs.delta_wi_beta_vi.inner_product(&w, n);
+    let two_r = FEp::from(2) * &r;
+    let t_coeffs: Vec<FEp> = (0..crs.delta_si_t_sk.len())
+        .map(|i| &two_r * coefficient(&u_x, i) + coefficient(&h_polinomial_from_sap, i))
+        .collect();
+
+    let b = f_w
+        + crs.delta_t_s_2.inner_product(&[&r * &r], n)
+        + crs.delta_si_t_sk.inner_product(&t_coeffs, n)
+        + crs.beta_t_sk.inner_product(&[r], n);
+
+    // flood the noise, which depends on the witness, and rerandomize c0
+    let (a_budget, b_budget) = proof_noise_budget(crs);
+    let a = rerandomize(&a, &crs.zeros, &crs.pk, flooding_bound(&a_budget, &crs.pk, crs.flooding_bits), rng);
+    let b = rerandomize(&b, &crs.zeros, &crs.pk, flooding_bound(&b_budget, &crs.pk, crs.flooding_bits), rng);
+
+    Ok(Proof { a, b })
+}
+
+/// Worst case noise of (a_proof, b_proof) for the circuit in `crs`, following the
+/// gadget inner products in `prove`.
+pub fn proof_noise_budget(crs: &CommonReferenceString) -> (NoiseBudget, NoiseBudget) {
+    let entry = crs.noise;
+
+    // delta (u(s) + r t(s))
+    let a_budget = crs.delta_si.inner_product_budget(&entry, crs.delta_si.len());
+
+    // f(w) + r^2 delta t(s)^2 + sum(delta s^i t(s) (2 r u_i + h_i)) + r beta t(s)
+    let b_budget = crs
+        .delta_wi_beta_vi
+        .inner_product_budget(&entry, crs.delta_wi_beta_vi.len())
+        .add(&crs.delta_t_s_2.inner_product_budget(&entry, 1))
+        .add(&crs.delta_si_t_sk.inner_product_budget(&entry, crs.delta_si_t_sk.len()))
+        .add(&crs.beta_t_sk.inner_product_budget(&entry, 1));
+
+    (a_budget, b_budget)
+}
+
+/// Noise of (a_proof, b_proof) after flooding, the proof decodes while both stay decodable.
+pub fn flooded_noise_budget(crs: &CommonReferenceString) -> (NoiseBudget, NoiseBudget) {
+    let zeros = zero_sum_budget(&crs.noise, crs.zeros.len());
+    let flood = |budget: NoiseBudget| budget.flood(&crs.pk, &zeros, flooding_bound(&budget, &crs.pk, crs.flooding_bits));
+    let (a_budget, b_budget) = proof_noise_budget(crs);
+
+    (flood(a_budget), flood(b_budget))
+}
+
+/// Proof of the QAP variant, one element more than `Proof` but no auxiliary variables.
+pub struct QapProof {
+    /// alpha + u(s) + r * delta
+    pub a: Encoding,
+    /// beta + v(s) + r' * delta
+    pub b: Encoding,
+    /// (sum_wit s_i (beta u_i(s) + alpha v_i(s) + w_i(s)) + h(s) t(s)) / delta + r' A + r B - r r' delta
+    pub c: Encoding,
+}
+
+/// QAP proof for public input `u` and R1CS witness `w`. Expanding r' A + r B,
+/// C = witness terms + h(s) t(s) / delta + r' alpha + r beta + r r' delta + r' u(s) + r v(s),
+/// so every CRS entry is scaled once by a decomposed coefficient. Fails when the
+/// assignment has the wrong length or does not satisfy the QAP.
+pub fn prove_qap<R: RngCore + CryptoRng>(
+    crs: &QapCommonReferenceString,
+    u: Vec<FEp>,
+    w: Vec<FEp>,
+    rng: &mut R,
+) -> Result<QapProof, AssignmentError> {
+    let n = crs.pk.n();
+    check_lengths(&u, &w, crs.qap.num_instance_variables, crs.qap.u_polynomials.len())?;
+    let full_instance: Vec<FEp> = [u, w.clone()].concat();
+
+    let u_x = crs.qap.u_polinomial(&full_instance);
+    let v_x = crs.qap.v_polinomial(&full_instance);
+    let h = crs.qap.h_polinomial(&full_instance)?;
 
-    let a_proof = delta_t_s + delta_v_s;
+    let r = sample_fr_elem_zp(rng);
+    let r_prime = sample_fr_elem_zp(rng);
 
-    // calculate B proof
-    let h_x_coeff = h_polinomial_from_sap.coefficients();
-    let mut delta_t_s_h_s: FE = FE::from(0);
-    for i in 0..h_x_coeff.len() {
-        delta_t_s_h_s += crs.delta_si[i] * FE::from_hex_unchecked(&h_x_coeff[i].representative().to_hex());
+    let a = crs.scalars.inner_product(&[FEp::from(1), FEp::from(0), r.clone()], n) + crs.si.inner_product(u_x.coefficients(), n);
+    let b = crs.scalars.inner_product(&[FEp::from(0), FEp::from(1), r_prime.clone()], n) + crs.si.inner_product(v_x.coefficients(), n);
+
+    let uv = u_x.mul_with_ref(&Polynomial::new_monomial(r_prime.clone(), 0)) + v_x.mul_with_ref(&Polynomial::new_monomial(r.clone(), 0));
+    let c = crs.witness_terms.inner_product(&w, n)
+        + crs.si_t_delta.inner_product(h.coefficients(), n)
+        + crs.scalars.inner_product(&[r_prime.clone(), r.clone(), &r * &r_prime], n)
+        + crs.si.inner_product(uv.coefficients(), n);
+
+    let (a_budget, b_budget, c_budget) = qap_proof_noise_budget(crs);
+    let flood = |budget: &NoiseBudget| flooding_bound(budget, &crs.pk, crs.flooding_bits);
+    Ok(QapProof {
+        a: rerandomize(&a, &crs.zeros, &crs.pk, flood(&a_budget), rng),
+        b: rerandomize(&b, &crs.zeros, &crs.pk, flood(&b_budget), rng),
+        c: rerandomize(&c, &crs.zeros, &crs.pk, flood(&c_budget), rng),
+    })
+}
+
+// `u` should hold the public inputs after s_0 = 1 and `u`, `w` together every
+// other of the `variables` variables of the program
+fn check_lengths(u: &[FEp], w: &[FEp], num_instance_variables: usize, variables: usize) -> Result<(), AssignmentError> {
+    if u.len() + 1 != num_instance_variables {
+        Err(AssignmentError::WrongLength { expected: num_instance_variables - 1, actual: u.len() })
+    } else if u.len() + w.len() + 1 != variables {
+        Err(AssignmentError::WrongLength { expected: variables - 1, actual: u.len() + w.len() })
+    } else {
+        Ok(())
     }
+}
+
+/// Worst case noise of the three elements of `prove_qap`, as `proof_noise_budget`.
+pub fn qap_proof_noise_budget(crs: &QapCommonReferenceString) -> (NoiseBudget, NoiseBudget, NoiseBudget) {
+    let entry = crs.noise;
+
+    // alpha + u(s) + r delta, same for B
+    let a_budget = crs
+        .scalars
+        .inner_product_budget(&entry, crs.scalars.len())
+        .add(&crs.si.inner_product_budget(&entry, crs.si.len()));
+    let b_budget = a_budget;
 
-    let b_proof = f_w + g_r + delta_t_s_h_s;
+    // witness terms + h(s) t(s) / delta + (r', r, r r') . (alpha, beta, delta) + r' u(s) + r v(s)
+    let c_budget = crs
+        .witness_terms
+        .inner_product_budget(&entry, crs.witness_terms.len())
+        .add(&crs.si_t_delta.inner_product_budget(&entry, crs.si_t_delta.len()))
+        .add(&a_budget);
+
+    (a_budget, b_budget, c_budget)
+}
+
+/// Noise of the three elements of `prove_qap` after flooding, as `flooded_noise_budget`.
+pub fn flooded_qap_noise_budget(crs: &QapCommonReferenceString) -> (NoiseBudget, NoiseBudget, NoiseBudget) {
+    let zeros = zero_sum_budget(&crs.noise, crs.zeros.len());
+    let flood = |budget: NoiseBudget| budget.flood(&crs.pk, &zeros, flooding_bound(&budget, &crs.pk, crs.flooding_bits));
+    let (a_budget, b_budget, c_budget) = qap_proof_noise_budget(crs);
+
+    (flood(a_budget), flood(b_budget), flood(c_budget))
+}
+
+/// Proof of the SSP variant.
+pub struct SspProof {
+    /// h'(s) = h(s) + 2 d v(s) + d^2 t(s)
+    pub h: Encoding,
+    /// v_wit(s) + d t(s)
+    pub v: Encoding,
+    /// beta * (v_wit(s) + d t(s))
+    pub b: Encoding,
+}
 
-    (a_proof, b_proof)
-}
\ No newline at end of file
+/// SSP proof for the public wires `u` and the other wires `w`, as 0/1 elements.
+/// The witness part of v is shifted by d t(x) for a random d, h changes accordingly:
+/// (v + d t)^2 - 1 = t (h + 2 d v + d^2 t). Fails when the wires have the wrong
+/// length or do not satisfy the SSP.
+pub fn prove_ssp<R: RngCore + CryptoRng>(
+    crs: &SspCommonReferenceString,
+    u: Vec<FEp>,
+    w: Vec<FEp>,
+    rng: &mut R,
+) -> Result<SspProof, AssignmentError> {
+    let n = crs.pk.n();
+    check_lengths(&u, &w, crs.ssp.num_instance_variables, crs.ssp.v_polynomials.len())?;
+    let full_instance: Vec<FEp> = [u, w.clone()].concat();
+
+    let v_x = crs.ssp.v_polinomial(&full_instance);
+    let h = crs.ssp.h_polinomial(&full_instance)?;
+
+    let d = sample_fr_elem_zp(rng);
+    let h = h + v_x.mul_with_ref(&Polynomial::new_monomial(FEp::from(2) * &d, 0))
+        + crs.ssp.target.mul_with_ref(&Polynomial::new_monomial(&d * &d, 0));
+
+    let h_s = crs.si.inner_product(h.coefficients(), n);
+    let v = crs.vi.inner_product(&w, n) + crs.scalars.inner_product(&[d.clone(), FEp::from(0)], n);
+    let b = crs.beta_vi.inner_product(&w, n) + crs.scalars.inner_product(&[FEp::from(0), d], n);
+
+    let (h_budget, v_budget) = ssp_proof_noise_budget(crs);
+    let flood = |budget: &NoiseBudget| flooding_bound(budget, &crs.pk, crs.flooding_bits);
+    Ok(SspProof {
+        h: rerandomize(&h_s, &crs.zeros, &crs.pk, flood(&h_budget), rng),
+        v: rerandomize(&v, &crs.zeros, &crs.pk, flood(&v_budget), rng),
+        b: rerandomize(&b, &crs.zeros, &crs.pk, flood(&v_budget), rng),
+    })
+}
+
+/// Worst case noise of h and of v (and b) in `prove_ssp`, as `proof_noise_budget`.
+pub fn ssp_proof_noise_budget(crs: &SspCommonReferenceString) -> (NoiseBudget, NoiseBudget) {
+    let entry = crs.noise;
+
+    // h'(s)
+    let h_budget = crs.si.inner_product_budget(&entry, crs.si.len());
+
+    // v_wit(s) + d t(s), b has the same bound
+    let v_budget = crs
+        .vi
+        .inner_product_budget(&entry, crs.vi.len())
+        .add(&crs.scalars.inner_product_budget(&entry, 1));
+
+    (h_budget, v_budget)
+}
+
+/// Noise of h and of v (and b) after flooding, as `flooded_noise_budget`.
+pub fn flooded_ssp_noise_budget(crs: &SspCommonReferenceString) -> (NoiseBudget, NoiseBudget) {
+    let zeros = zero_sum_budget(&crs.noise, crs.zeros.len());
+    let flood = |budget: NoiseBudget| budget.flood(&crs.pk, &zeros, flooding_bound(&budget, &crs.pk, crs.flooding_bits));
+    let (h_budget, v_budget) = ssp_proof_noise_budget(crs);
+
+    (flood(h_budget), flood(v_budget))
+}
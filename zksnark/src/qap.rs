@@ -0,0 +1,175 @@
+use crate::{
+    common::{Fp, FEp},
+    r1cs::{SparseMatrix, SparseR1CS, R1CS},
+    sap::{coset_evaluations, coset_quotient, combine, domain, lagrange_basis, QuotientError, SparseEvaluations},
+};
+use lambdaworks_math::polynomial::Polynomial;
+
+/// QAP of an R1CS: (sum_i s_i u_i(x)) (sum_i s_i v_i(x)) - sum_i s_i w_i(x) = h(x) t(x).
+/// Unlike the SAP, every R1CS constraint takes a single row and no variable is added.
+/// Rows m + i are the constraints s_i * 0 = 0 for every public input i, they make the
+/// u_i of the public inputs linearly independent. The domain is a power-of-two subgroup
+/// of size N and t(x) = x^N - 1, as for the SAP.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuadraticArithmeticProgram {
+    /// Number of public input variables, including the leading `1`
+    pub num_instance_variables: usize,
+    /// Number of private (a.k.a. witness) variables
+    pub num_r1cs_witness_variables: usize,
+    pub num_r1cs_constraints: usize,
+    pub u_polynomials: Vec<SparseEvaluations>,
+    pub v_polynomials: Vec<SparseEvaluations>,
+    pub w_polynomials: Vec<SparseEvaluations>,
+    pub target: Polynomial<FEp>,
+    pub r1cs: SparseR1CS,
+}
+
+impl QuadraticArithmeticProgram {
+    pub fn r1cs_to_qap(r1cs: R1CS) -> Self {
+        Self::sparse_r1cs_to_qap(SparseR1CS::from(&r1cs))
+    }
+
+    /// QAP of an R1CS in sparse form, the work is linear in the nonzero entries of A, B, C.
+    pub fn sparse_r1cs_to_qap(r1cs: SparseR1CS) -> Self {
+        let num_r1cs_constraints = r1cs.num_of_constraints();
+        let num_instance_variables = r1cs.num_instance_variables();
+        let num_variables = r1cs.witness_size();
+        let domain_size = (num_r1cs_constraints + num_instance_variables).next_power_of_two();
+
+        // column i of a matrix is variable i over the constraints, rows come in order
+        let columns = |matrix: &SparseMatrix| {
+            let mut columns = vec![SparseEvaluations::new(Vec::new()); num_variables];
+            for i in 0..num_r1cs_constraints {
+                for (j, v) in matrix.row(i) {
+                    columns[j].entries.push((i, v.clone()));
+                }
+            }
+            columns
+        };
+        let mut us = columns(&r1cs.a);
+        let vs = columns(&r1cs.b);
+        let ws = columns(&r1cs.c);
+
+        for (i, u) in us.iter_mut().take(num_instance_variables).enumerate() {
+            u.entries.push((num_r1cs_constraints + i, FEp::one()));
+        }
+
+        let t = Polynomial::new_monomial(FEp::one(), domain_size) - Polynomial::new_monomial(FEp::one(), 0);
+
+        QuadraticArithmeticProgram {
+            num_instance_variables,
+            num_r1cs_witness_variables: r1cs.num_r1cs_aux_variables(),
+            num_r1cs_constraints,
+            u_polynomials: us,
+            v_polynomials: vs,
+            w_polynomials: ws,
+            target: t,
+            r1cs,
+        }
+    }
+
+    /// Size N of the evaluation domain, t(x) = x^N - 1.
+    pub fn domain_size(&self) -> usize {
+        self.target.degree()
+    }
+
+    pub fn domain(&self) -> Vec<FEp> {
+        domain(self.domain_size())
+    }
+
+    /// L_j(x) for every point w^j of the domain, see `sap::lagrange_basis`.
+    pub fn lagrange_basis_at(&self, x: &FEp) -> Vec<FEp> {
+        lagrange_basis(self.domain_size(), x)
+    }
+
+    /// u(x) = u_0(x) + sum_i c_i u_i(x) in coefficient form, `cs` is s_1, .. with s_0 = 1 implicit.
+    pub fn u_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
+        interpolate(&self.u_polynomials, cs, self.domain_size())
+    }
+
+    /// v(x) = v_0(x) + sum_i c_i v_i(x) in coefficient form.
+    pub fn v_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
+        interpolate(&self.v_polynomials, cs, self.domain_size())
+    }
+
+    /// w(x) = w_0(x) + sum_i c_i w_i(x) in coefficient form.
+    pub fn w_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
+        interpolate(&self.w_polynomials, cs, self.domain_size())
+    }
+
+    /// h(x) = (u(x) v(x) - w(x)) / t(x) through a coset FFT, see `sap::coset_quotient`.
+    pub fn h_polinomial(&self, cs: &[FEp]) -> Result<Polynomial<FEp>, QuotientError> {
+        let n = self.domain_size();
+        let u_evals = coset_evaluations(&self.u_polinomial(cs), n);
+        let v_evals = coset_evaluations(&self.v_polinomial(cs), n);
+        let w_evals = coset_evaluations(&self.w_polinomial(cs), n);
+
+        let p_evals = u_evals.iter().zip(&v_evals).zip(&w_evals).map(|((u, v), w)| u * v - w).collect();
+        coset_quotient(p_evals, n)
+    }
+
+    /// u(x) v(x) - w(x) in coefficient form.
+    pub fn p_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
+        self.u_polinomial(cs) * self.v_polinomial(cs) - self.w_polinomial(cs)
+    }
+}
+
+fn interpolate(polys: &[SparseEvaluations], cs: &[FEp], n: usize) -> Polynomial<FEp> {
+    Polynomial::interpolate_fft::<Fp>(&combine(polys, cs, n)).expect("domain should fit in the two-adic subgroup")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sap::SquareArithmeticProgram,
+        test_ex::{new_test_r1cs, new_test_r1cs_assignment},
+    };
+
+    fn assignment() -> Vec<FEp> {
+        let (u, w) = new_test_r1cs_assignment();
+        [u, w].concat()
+    }
+
+    #[test]
+    fn qap_quotient_divides_for_satisfying_assignment() {
+        let qap = QuadraticArithmeticProgram::r1cs_to_qap(new_test_r1cs());
+        let cs = assignment();
+
+        let h = qap.h_polinomial(&cs).unwrap();
+        assert_eq!(h.mul_with_ref(&qap.target), qap.p_polinomial(&cs));
+
+        let mut bad = cs.clone();
+        bad[4] += FEp::one();
+        assert_eq!(qap.h_polinomial(&bad), Err(QuotientError::NonZeroRemainder));
+    }
+
+    #[test]
+    fn qap_is_smaller_than_sap() {
+        let r1cs = new_test_r1cs();
+        let qap = QuadraticArithmeticProgram::r1cs_to_qap(r1cs.clone());
+        let sap = SquareArithmeticProgram::r1cs_to_sap(r1cs.clone());
+
+        assert_eq!(qap.u_polynomials.len(), r1cs.witness_size());
+        assert!(qap.u_polynomials.len() < sap.u_polynomials.len());
+        assert!(qap.domain_size() <= sap.domain_size());
+        assert!(qap.domain().iter().all(|x| qap.target.evaluate(x) == FEp::zero()));
+    }
+
+    #[test]
+    fn sparse_and_dense_r1cs_give_the_same_qap() {
+        let r1cs = new_test_r1cs();
+        let sparse = SparseR1CS::from(&r1cs);
+
+        let qap = QuadraticArithmeticProgram::sparse_r1cs_to_qap(sparse.clone());
+        assert_eq!(qap, QuadraticArithmeticProgram::r1cs_to_qap(r1cs.clone()));
+        assert_eq!(qap.r1cs, sparse);
+
+        // column j of A over the constraints
+        let (a, _, _) = r1cs.constraints_to_matrix();
+        for j in qap.num_instance_variables..r1cs.witness_size() {
+            let column: Vec<FEp> = a.iter().map(|row| row[j].clone()).collect();
+            assert_eq!(qap.u_polynomials[j], SparseEvaluations::from_dense(&column));
+        }
+    }
+}
@@ -6,6 +6,7 @@ pub enum CreationError {
     MatrixesSizeMismatch,
     /// Number of IOs should be less than witness size - 1
     InputOutputTooBig,
+    NoConstraints,
 }
 
 /**
@@ -51,7 +52,9 @@ impl R1CS {
         number_of_inputs: usize,
         number_of_outputs: usize,
     ) -> Result<Self, CreationError> {
-        // 
+        if constraints.is_empty() {
+            return Err(CreationError::NoConstraints);
+        }
         let witness_size = constraints[0].a.len();
         // println!("Constraint [0] = {}", constraints[0]);
         // println!("Witness Size = {}", witness_size.clone());
@@ -194,6 +197,165 @@ impl Constraint {
     }
 }
 
+/// Matrix in compressed sparse row (CSR) form: the nonzero entries of row i are
+/// `columns[row_ptr[i]..row_ptr[i + 1]]` with the matching `values`, by increasing column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseMatrix {
+    pub num_rows: usize,
+    pub num_cols: usize,
+    pub row_ptr: Vec<usize>,
+    pub columns: Vec<usize>,
+    pub values: Vec<FEp>,
+}
+
+impl SparseMatrix {
+    /// From coordinate (COO) triplets (row, column, value) in any order, repeated
+    /// coordinates are summed and zeros dropped.
+    pub fn from_triplets(num_rows: usize, num_cols: usize, mut triplets: Vec<(usize, usize, FEp)>) -> Self {
+        assert!(triplets.iter().all(|(i, j, _)| *i < num_rows && *j < num_cols), "entry out of range");
+        triplets.sort_by_key(|(i, j, _)| (*i, *j));
+
+        let mut entries: Vec<(usize, usize, FEp)> = Vec::with_capacity(triplets.len());
+        for (i, j, v) in triplets {
+            match entries.last_mut() {
+                Some((li, lj, lv)) if *li == i && *lj == j => *lv = &*lv + v,
+                _ => entries.push((i, j, v)),
+            }
+        }
+        entries.retain(|(_, _, v)| *v != FEp::zero());
+
+        let mut row_ptr = vec![0; num_rows + 1];
+        for (i, _, _) in &entries {
+            row_ptr[i + 1] += 1;
+        }
+        for i in 0..num_rows {
+            row_ptr[i + 1] += row_ptr[i];
+        }
+        let (columns, values) = entries.into_iter().map(|(_, j, v)| (j, v)).unzip();
+
+        SparseMatrix { num_rows, num_cols, row_ptr, columns, values }
+    }
+
+    pub fn from_dense(rows: &[Vec<FEp>], num_cols: usize) -> Self {
+        let triplets = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| row.iter().enumerate().filter(|(_, v)| **v != FEp::zero()).map(move |(j, v)| (i, j, v.clone())))
+            .collect();
+        SparseMatrix::from_triplets(rows.len(), num_cols, triplets)
+    }
+
+    pub fn to_dense(&self) -> Vec<Vec<FEp>> {
+        (0..self.num_rows)
+            .map(|i| {
+                let mut row = vec![FEp::zero(); self.num_cols];
+                for (j, v) in self.row(i) {
+                    row[j] = v.clone();
+                }
+                row
+            })
+            .collect()
+    }
+
+    /// (column, value) of the nonzero entries of row i.
+    pub fn row(&self, i: usize) -> impl Iterator<Item = (usize, &FEp)> {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        self.columns[range.clone()].iter().copied().zip(&self.values[range])
+    }
+
+    /// Number of nonzero entries.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// M s, one inner product per row.
+    pub fn mul_vector(&self, s: &[FEp]) -> Vec<FEp> {
+        (0..self.num_rows)
+            .map(|i| self.row(i).fold(FEp::zero(), |acc, (j, v)| acc + v * &s[j]))
+            .collect()
+    }
+}
+
+/// R1CS with A, B, C stored as sparse matrices, one row per constraint, and the
+/// same variable layout as `R1CS`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseR1CS {
+    pub a: SparseMatrix,
+    pub b: SparseMatrix,
+    pub c: SparseMatrix,
+    pub number_of_inputs: usize,
+    pub number_of_outputs: usize,
+}
+
+impl SparseR1CS {
+    pub fn new(
+        a: SparseMatrix,
+        b: SparseMatrix,
+        c: SparseMatrix,
+        number_of_inputs: usize,
+        number_of_outputs: usize,
+    ) -> Result<Self, CreationError> {
+        let same_shape = |m: &SparseMatrix| m.num_rows == a.num_rows && m.num_cols == a.num_cols;
+        if a.num_rows == 0 {
+            Err(CreationError::NoConstraints)
+        } else if !same_shape(&b) || !same_shape(&c) {
+            Err(CreationError::MatrixesSizeMismatch)
+        } else if number_of_inputs + number_of_outputs >= a.num_cols {
+            Err(CreationError::InputOutputTooBig)
+        } else {
+            Ok(Self { a, b, c, number_of_inputs, number_of_outputs })
+        }
+    }
+
+    pub fn to_dense(&self) -> Result<R1CS, CreationError> {
+        R1CS::new_with_matrixes(self.a.to_dense(), self.b.to_dense(), self.c.to_dense(), self.number_of_inputs, self.number_of_outputs)
+    }
+
+    /// False unless `s` has one value per column and satisfies every constraint.
+    pub fn verify_solution(&self, s: &[FEp]) -> bool {
+        if s.len() != self.witness_size() {
+            return false;
+        }
+        let (a, b, c) = (self.a.mul_vector(s), self.b.mul_vector(s), self.c.mul_vector(s));
+        a.iter().zip(&b).zip(&c).all(|((a, b), c)| a * b == *c)
+    }
+
+    pub fn num_of_constraints(&self) -> usize {
+        self.a.num_rows
+    }
+
+    // include leading "1"
+    pub fn num_instance_variables(&self) -> usize {
+        self.number_of_inputs + 1
+    }
+
+    pub fn num_r1cs_aux_variables(&self) -> usize {
+        self.witness_size() - 1 - self.number_of_inputs
+    }
+
+    pub fn witness_size(&self) -> usize {
+        self.a.num_cols
+    }
+}
+
+impl From<&R1CS> for SparseR1CS {
+    fn from(r1cs: &R1CS) -> Self {
+        let n = r1cs.witness_size();
+        let rows = |f: fn(&Constraint) -> &Vec<FEp>| {
+            let dense: Vec<Vec<FEp>> = r1cs.constraints.iter().map(|c| f(c).clone()).collect();
+            SparseMatrix::from_dense(&dense, n)
+        };
+
+        SparseR1CS {
+            a: rows(|c| &c.a),
+            b: rows(|c| &c.b),
+            c: rows(|c| &c.c),
+            number_of_inputs: r1cs.number_of_inputs,
+            number_of_outputs: r1cs.number_of_outputs,
+        }
+    }
+}
+
 // вычисляем скалярное произведение двух векторов
 pub fn inner_product(v1: &[FEp], v2: &[FEp]) -> FEp {
     v1.iter()
@@ -310,6 +472,47 @@ pub mod tests {
         assert!(!new_test_r1cs().verify_solution(&solution))
     }
 
+    #[test]
+    fn sparse_r1cs_round_trips_and_verifies() {
+        let r1cs = new_test_r1cs();
+        let sparse = SparseR1CS::from(&r1cs);
+
+        assert_eq!(sparse.a.nnz() + sparse.b.nnz() + sparse.c.nnz(), 7);
+        assert_eq!(sparse.to_dense(), Ok(r1cs.clone()));
+        assert_eq!(sparse.num_r1cs_aux_variables(), r1cs.num_r1cs_aux_variables());
+        assert!(sparse.verify_solution(&test_solution()));
+        assert!(!sparse.verify_solution(&[0, 1, 1, 5, 10, 10, 19].map(FEp::from)));
+        assert!(!sparse.verify_solution(&test_solution()[..6]));
+    }
+
+    #[test]
+    fn sparse_r1cs_rejects_bad_shapes() {
+        let empty = SparseMatrix::from_triplets(1, 0, vec![]);
+        assert_eq!(
+            SparseR1CS::new(empty.clone(), empty.clone(), empty, 0, 0),
+            Err(CreationError::InputOutputTooBig)
+        );
+
+        let m = SparseMatrix::from_triplets(1, 3, vec![(0, 0, FEp::from(1))]);
+        assert_eq!(SparseR1CS::new(m.clone(), m.clone(), m.clone(), 1, 2), Err(CreationError::InputOutputTooBig));
+        assert!(SparseR1CS::new(m.clone(), m.clone(), m, 1, 1).is_ok());
+    }
+
+    #[test]
+    fn triplets_are_summed_into_csr() {
+        let m = SparseMatrix::from_triplets(
+            3,
+            4,
+            vec![(2, 1, FEp::from(5)), (0, 3, FEp::from(1)), (2, 1, FEp::from(2)), (0, 0, FEp::from(4)), (1, 2, FEp::from(0))],
+        );
+
+        assert_eq!(m.row_ptr, vec![0, 2, 2, 3]);
+        assert_eq!(m.columns, vec![0, 3, 1]);
+        assert_eq!(m.values, vec![FEp::from(4), FEp::from(1), FEp::from(7)]);
+        assert_eq!(SparseMatrix::from_dense(&m.to_dense(), 4), m);
+        assert_eq!(m.mul_vector(&[1, 2, 3, 4].map(FEp::from)), vec![FEp::from(8), FEp::from(0), FEp::from(14)]);
+    }
+
     fn test_solution() -> Vec<FEp> {
         vec![
             FEp::from(0),
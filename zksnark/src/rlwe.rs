@@ -0,0 +1,249 @@
+use crate::{
+    common::*,
+    encoding::{Encoding, EncodingScheme},
+    gaussian::{sample_uniform_zq, DiscreteGaussian, SamplingMode},
+    noise::*,
+    params::{LweParams, ParamsError},
+    rq::{is_ring_dimension, Rq},
+};
+use rand::{CryptoRng, RngCore};
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Ring-LWE encoding (c0, c1) = (-a, a s + p e + m) over R_q = Z_q[X] / (X^n + 1).
+/// The n coefficients of m are n messages of Z_p sharing one `a`, and `extract`
+/// turns each of them back into a scalar encoding under the coefficients of s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RingEncoding {
+    pub c0: Rq,
+    pub c1: Rq,
+}
+
+impl RingEncoding {
+    pub fn new(c0: Rq, c1: Rq) -> Self {
+        RingEncoding { c0, c1 }
+    }
+
+    pub fn zero(n: usize) -> Self {
+        RingEncoding::new(Rq::zero(n), Rq::zero(n))
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.c1.dimension()
+    }
+
+    /// c1 + c0 s = p e + m
+    pub fn phase(&self, s: &Rq) -> Rq {
+        &self.c1 + &(&self.c0 * s)
+    }
+
+    pub fn scale(&self, k: &FE) -> Self {
+        RingEncoding::new(self.c0.scale(k), self.c1.scale(k))
+    }
+
+    /// Scalar encoding of the i-th slot under the coefficient vector of s,
+    /// using sum_j c0_j s_(i-j) with X^n = -1 for the wrapped terms.
+    pub fn extract(&self, i: usize) -> Encoding {
+        let n = self.dimension();
+        let c0 = self.c0.coefficients();
+        let row = (0..n).map(|j| if j <= i { c0[i - j] } else { -c0[n + i - j] }).collect();
+
+        Encoding::new(row, self.c1.coefficients()[i])
+    }
+}
+
+/// Standalone ring backend for `EncodingScheme`. `setup`, `prove` and `verify` still
+/// run over scalar `LWE` encodings (packed only through `setup_with_packing`), so
+/// nothing in the CRS is ring-packed yet.
+pub struct RingLwe {
+    pk: LweParams,
+    gaussian: DiscreteGaussian,
+}
+
+impl RingLwe {
+    pub fn new(pk: LweParams) -> Result<RingLwe, ParamsError> {
+        RingLwe::new_with_mode(pk, SamplingMode::VariableTime)
+    }
+
+    pub fn new_with_mode(pk: LweParams, mode: SamplingMode) -> Result<RingLwe, ParamsError> {
+        if !is_ring_dimension(pk.n()) {
+            return Err(ParamsError::InvalidRingDimension);
+        }
+        let gaussian = DiscreteGaussian::new_with_mode(pk.std_dev(), mode);
+
+        Ok(RingLwe { pk, gaussian })
+    }
+
+    pub fn params(&self) -> &LweParams {
+        &self.pk
+    }
+
+    pub fn mode(&self) -> SamplingMode {
+        self.gaussian.mode()
+    }
+
+    pub fn key_gen<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Rq {
+        Rq::new(self.pk.secret().sample(self.pk.n(), &self.gaussian, rng))
+    }
+
+    /// Encodes up to n messages, missing slots encode 0.
+    pub fn encode<R: RngCore + CryptoRng>(&self, m: Vec<FEp>, s: &Rq, rng: &mut R) -> RingEncoding {
+        let n = self.pk.n();
+        assert!(m.len() <= n, "at most n messages fit in one ring encoding");
+
+        let a = self.sample_rq(rng);
+        let p = FE::from(self.pk.p());
+        let noisy: Vec<FE> = (0..n)
+            .map(|i| p * self.gaussian.sample_fe(rng) + m.get(i).map_or(FE::from(0), fep_to_fe))
+            .collect();
+
+        // a*s + p*e + m
+        let c1 = &(&a * s) + &Rq::new(noisy);
+        RingEncoding::new(-a, c1)
+    }
+
+    /// Encodes any number of messages into ceil(len / n) ring encodings,
+    /// message k lands in slot k % n of encoding k / n.
+    pub fn encode_slice<R: RngCore + CryptoRng>(&self, m: &[FEp], s: &Rq, rng: &mut R) -> Vec<RingEncoding> {
+        m.chunks(self.pk.n()).map(|chunk| self.encode(chunk.to_vec(), s, rng)).collect()
+    }
+
+    /// Decodes a fresh encoding into its n slots.
+    pub fn decode(&self, s: &Rq, c: &RingEncoding) -> Result<Vec<FEp>, DecodeError> {
+        self.decode_with_budget(s, c, &NoiseBudget::fresh(&self.pk))
+    }
+
+    /// Decodes an encoding whose noise in every slot is bounded by `budget`.
+    pub fn decode_with_budget(&self, s: &Rq, c: &RingEncoding, budget: &NoiseBudget) -> Result<Vec<FEp>, DecodeError> {
+        if !budget.is_decodable(&self.pk) {
+            return Err(DecodeError::BudgetExceeded);
+        }
+
+        c.phase(s)
+            .coefficients()
+            .iter()
+            .map(|x| {
+                let x = centered(x);
+                if x.unsigned_abs() > budget.bound() {
+                    return Err(DecodeError::NoiseOverflow);
+                }
+                Ok(FEp::from(x.rem_euclid(self.pk.p() as i128) as u64))
+            })
+            .collect()
+    }
+
+    fn sample_rq<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Rq {
+        Rq::new((0..self.pk.n()).map(|_| sample_uniform_zq(self.mode(), rng)).collect())
+    }
+}
+
+impl EncodingScheme for RingLwe {
+    type Secret = Rq;
+    type Message = Vec<FEp>;
+    type Ciphertext = RingEncoding;
+
+    fn key_gen<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Rq {
+        RingLwe::key_gen(self, rng)
+    }
+
+    fn encode<R: RngCore + CryptoRng>(&self, m: Vec<FEp>, s: &Rq, rng: &mut R) -> RingEncoding {
+        RingLwe::encode(self, m, s, rng)
+    }
+
+    fn decode(&self, s: &Rq, c: &RingEncoding) -> Result<Vec<FEp>, DecodeError> {
+        RingLwe::decode(self, s, c)
+    }
+}
+
+impl Add<&RingEncoding> for &RingEncoding {
+    type Output = RingEncoding;
+
+    fn add(self, other: &RingEncoding) -> RingEncoding {
+        RingEncoding::new(&self.c0 + &other.c0, &self.c1 + &other.c1)
+    }
+}
+
+impl Sub<&RingEncoding> for &RingEncoding {
+    type Output = RingEncoding;
+
+    fn sub(self, other: &RingEncoding) -> RingEncoding {
+        RingEncoding::new(&self.c0 - &other.c0, &self.c1 - &other.c1)
+    }
+}
+
+impl Neg for &RingEncoding {
+    type Output = RingEncoding;
+
+    fn neg(self) -> RingEncoding {
+        RingEncoding::new(-&self.c0, -&self.c1)
+    }
+}
+
+impl Mul<&FEp> for &RingEncoding {
+    type Output = RingEncoding;
+
+    fn mul(self, k: &FEp) -> RingEncoding {
+        self.scale(&fep_to_fe(k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lwe::LWE;
+
+    fn ring_toy() -> LweParams {
+        LweParams::new(16, P_MODULUS, Q_MODULUS, 0.000000000000001).unwrap()
+    }
+
+    #[test]
+    fn rejects_non_power_of_two_dimension() {
+        assert!(matches!(RingLwe::new(LweParams::toy()), Err(ParamsError::InvalidRingDimension)));
+    }
+
+    #[test]
+    fn encode_decode_all_slots() {
+        let mut rng = seeded_rng(41);
+        let rlwe = RingLwe::new(ring_toy()).unwrap();
+        let s = rlwe.key_gen(&mut rng);
+
+        let m: Vec<FEp> = (0..16).map(|i| FEp::from(i * 1000 + 7)).collect();
+        let c = rlwe.encode(m.clone(), &s, &mut rng);
+        assert_eq!(rlwe.decode(&s, &c), Ok(m));
+
+        let short = rlwe.encode(vec![FEp::from(5)], &s, &mut rng);
+        assert_eq!(rlwe.decode(&s, &short).unwrap()[..2], [FEp::from(5), FEp::from(0)]);
+    }
+
+    #[test]
+    fn slot_wise_homomorphism() {
+        let mut rng = seeded_rng(42);
+        let rlwe = RingLwe::new(ring_toy()).unwrap();
+        let s = rlwe.key_gen(&mut rng);
+
+        let m1: Vec<FEp> = (0..16).map(FEp::from).collect();
+        let m2: Vec<FEp> = (0..16).map(|i| FEp::from(100 * i)).collect();
+        let (c1, c2) = (rlwe.encode(m1.clone(), &s, &mut rng), rlwe.encode(m2.clone(), &s, &mut rng));
+
+        let budget = NoiseBudget::linear_combination(&[NoiseBudget::fresh(rlwe.params()); 2], &[FEp::from(3), FEp::from(1)]);
+        let expected: Vec<FEp> = m1.iter().zip(&m2).map(|(x, y)| FEp::from(3) * x + y).collect();
+        assert_eq!(rlwe.decode_with_budget(&s, &(&(&c1 * &FEp::from(3)) + &c2), &budget), Ok(expected));
+    }
+
+    #[test]
+    fn extracted_slots_decode_as_scalar_encodings() {
+        let mut rng = seeded_rng(43);
+        let rlwe = RingLwe::new(ring_toy()).unwrap();
+        let s = rlwe.key_gen(&mut rng);
+        let lwe = LWE::new(ring_toy());
+        let s_vec = s.coefficients().to_vec();
+
+        let m: Vec<FEp> = (0..40).map(|i| FEp::from(3 * i + 1)).collect();
+        let packed = rlwe.encode_slice(&m, &s, &mut rng);
+        assert_eq!(packed.len(), 3);
+
+        for (k, mk) in m.iter().enumerate() {
+            let scalar = packed[k / 16].extract(k % 16);
+            assert_eq!(lwe.decode(&s_vec, &scalar), Ok(mk.clone()));
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::common::*;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Generator of the multiplicative group of the Goldilocks field.
+const GOLDILOCKS_GENERATOR: u64 = 7;
+
+/// q - 1 = 2^32 * (2^32 - 1), so X^n + 1 splits completely for n <= 2^31.
+const GOLDILOCKS_TWO_ADICITY: u32 = 32;
+
+/// Element of the anticyclic ring R_q = Z_q[X] / (X^n + 1), n a power of two,
+/// stored as its n coefficients from X^0 to X^(n-1).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rq {
+    coeffs: Vec<FE>,
+}
+
+impl Rq {
+    pub fn new(coeffs: Vec<FE>) -> Self {
+        assert!(is_ring_dimension(coeffs.len()), "n should be a power of two not above 2^31");
+        Rq { coeffs }
+    }
+
+    pub fn zero(n: usize) -> Self {
+        Rq::new(vec![FE::from(0); n])
+    }
+
+    /// c * X^degree reduced with X^n = -1.
+    pub fn new_monomial(c: FE, degree: usize, n: usize) -> Self {
+        let mut coeffs = vec![FE::from(0); n];
+        coeffs[degree % n] = if (degree / n) & 1 == 0 { c } else { -c };
+        Rq::new(coeffs)
+    }
+
+    pub fn coefficients(&self) -> &[FE] {
+        &self.coeffs
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.coeffs.len()
+    }
+
+    pub fn scale(&self, k: &FE) -> Self {
+        Rq { coeffs: self.coeffs.iter().map(|x| x * k).collect() }
+    }
+
+    /// Negacyclic product through the NTT: with psi a primitive 2n-th root of unity,
+    /// a(psi X) b(psi X) is a cyclic convolution, computed with omega = psi^2.
+    pub fn mul_ntt(&self, other: &Rq) -> Rq {
+        let n = self.dimension();
+        assert_eq!(n, other.dimension(), "ring elements should have the same dimension");
+
+        let psi = root_of_unity(2 * n as u64);
+        let psi_powers = powers(&psi, n);
+        let omega = psi.square();
+
+        let twist = |a: &Rq| -> Vec<FE> { a.coeffs.iter().zip(&psi_powers).map(|(x, w)| x * w).collect() };
+        let mut a = twist(self);
+        let mut b = twist(other);
+        ntt(&mut a, &omega);
+        ntt(&mut b, &omega);
+
+        let mut c: Vec<FE> = a.iter().zip(&b).map(|(x, y)| x * y).collect();
+        ntt(&mut c, &inverse(&omega));
+
+        // undo the twist and the n scaling of the inverse transform
+        let n_inv = inverse(&FE::from(n as u64));
+        let psi_inv_powers = powers(&inverse(&psi), n);
+        Rq { coeffs: c.iter().zip(&psi_inv_powers).map(|(x, w)| x * w * n_inv).collect() }
+    }
+
+    /// Quadratic schoolbook product, reference for `mul_ntt`.
+    pub fn mul_schoolbook(&self, other: &Rq) -> Rq {
+        let n = self.dimension();
+        assert_eq!(n, other.dimension(), "ring elements should have the same dimension");
+
+        let mut coeffs = vec![FE::from(0); n];
+        for (i, x) in self.coeffs.iter().enumerate() {
+            for (j, y) in other.coeffs.iter().enumerate() {
+                if i + j < n {
+                    coeffs[i + j] += x * y;
+                } else {
+                    coeffs[i + j - n] = coeffs[i + j - n] - x * y;
+                }
+            }
+        }
+        Rq { coeffs }
+    }
+}
+
+/// n is a power of two and X^n + 1 splits over Goldilocks.
+pub fn is_ring_dimension(n: usize) -> bool {
+    n.is_power_of_two() && n.trailing_zeros() < GOLDILOCKS_TWO_ADICITY
+}
+
+/// Primitive root of unity of order `order`, a power of two not above 2^32.
+pub fn root_of_unity(order: u64) -> FE {
+    assert!(order.is_power_of_two() && order.trailing_zeros() <= GOLDILOCKS_TWO_ADICITY);
+    FE::from(GOLDILOCKS_GENERATOR).pow((Q_MODULUS - 1) / order)
+}
+
+/// x^(q - 2), the inverse of a nonzero x.
+fn inverse(x: &FE) -> FE {
+    x.pow(Q_MODULUS - 2)
+}
+
+fn powers(x: &FE, n: usize) -> Vec<FE> {
+    let mut out = Vec::with_capacity(n);
+    let mut acc = FE::from(1);
+    for _ in 0..n {
+        out.push(acc);
+        acc *= x;
+    }
+    out
+}
+
+/// In-place iterative radix-2 transform, a_i <- sum_j a_j omega^(ij), for omega
+/// of order a.len(). The inverse transform is `ntt` with omega^-1 followed by a 1/n scaling.
+pub fn ntt(a: &mut [FE], omega: &FE) {
+    let n = a.len();
+    let bits = n.trailing_zeros();
+    if n <= 1 {
+        return;
+    }
+
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            a.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let w_len = omega.pow((n / len) as u64);
+        let twiddles = powers(&w_len, len / 2);
+        for chunk in a.chunks_mut(len) {
+            let (lo, hi) = chunk.split_at_mut(len / 2);
+            for ((x, y), w) in lo.iter_mut().zip(hi.iter_mut()).zip(&twiddles) {
+                let t = *y * w;
+                (*x, *y) = (*x + t, *x - t);
+            }
+        }
+        len *= 2;
+    }
+}
+
+impl Add<&Rq> for &Rq {
+    type Output = Rq;
+
+    fn add(self, other: &Rq) -> Rq {
+        assert_eq!(self.dimension(), other.dimension(), "ring elements should have the same dimension");
+        Rq { coeffs: self.coeffs.iter().zip(&other.coeffs).map(|(x, y)| x + y).collect() }
+    }
+}
+
+impl Add<Rq> for Rq {
+    type Output = Rq;
+
+    fn add(self, other: Rq) -> Rq {
+        &self + &other
+    }
+}
+
+impl Sub<&Rq> for &Rq {
+    type Output = Rq;
+
+    fn sub(self, other: &Rq) -> Rq {
+        self + &(-other)
+    }
+}
+
+impl Sub<Rq> for Rq {
+    type Output = Rq;
+
+    fn sub(self, other: Rq) -> Rq {
+        &self - &other
+    }
+}
+
+impl Neg for &Rq {
+    type Output = Rq;
+
+    fn neg(self) -> Rq {
+        Rq { coeffs: self.coeffs.iter().map(|x| -x).collect() }
+    }
+}
+
+impl Neg for Rq {
+    type Output = Rq;
+
+    fn neg(self) -> Rq {
+        -&self
+    }
+}
+
+impl Mul<&Rq> for &Rq {
+    type Output = Rq;
+
+    fn mul(self, other: &Rq) -> Rq {
+        self.mul_ntt(other)
+    }
+}
+
+impl Mul<Rq> for Rq {
+    type Output = Rq;
+
+    fn mul(self, other: Rq) -> Rq {
+        &self * &other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_rq(n: usize, seed: u64) -> Rq {
+        let mut rng = seeded_rng(seed);
+        Rq::new((0..n).map(|_| sample_fr_elem_zq(&mut rng)).collect())
+    }
+
+    #[test]
+    fn ntt_product_matches_schoolbook() {
+        for n in [1, 2, 8, 64] {
+            let (a, b) = (random_rq(n, 1), random_rq(n, 2));
+            assert_eq!(a.mul_ntt(&b), a.mul_schoolbook(&b));
+        }
+    }
+
+    #[test]
+    fn x_to_the_n_is_minus_one() {
+        let n = 16;
+        let x = Rq::new_monomial(FE::from(1), 1, n);
+        let x_n_minus_1 = Rq::new_monomial(FE::from(1), n - 1, n);
+
+        assert_eq!(&x * &x_n_minus_1, Rq::new_monomial(-FE::from(1), 0, n));
+        assert_eq!(Rq::new_monomial(FE::from(3), n + 2, n), Rq::new_monomial(-FE::from(3), 2, n));
+    }
+
+    #[test]
+    fn roots_of_unity_have_exact_order() {
+        let w = root_of_unity(1 << 10);
+        assert_eq!(w.pow(1u64 << 10), FE::from(1));
+        assert_ne!(w.pow(1u64 << 9), FE::from(1));
+    }
+}
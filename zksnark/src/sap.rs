@@ -1,9 +1,9 @@
 use crate::{
-    common::FEp,
-    r1cs::R1CS,
+    common::{Fp, FEp},
+    r1cs::{SparseR1CS, R1CS},
 };
-use std::convert::From;
-use lambdaworks_math::polynomial::Polynomial;
+use std::{collections::BTreeMap, convert::From, fmt::Display};
+use lambdaworks_math::{field::traits::IsFFTField, polynomial::Polynomial};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SquareArithmeticProgram {
@@ -14,10 +14,11 @@ pub struct SquareArithmeticProgram {
     pub num_r1cs_witness_variables: usize, 
     /// Number of constraints in the underlying R1CS.
     pub num_r1cs_constraints: usize,
-    pub u_polynomials: Vec<Polynomial<FEp>>,
-    pub w_polynomials: Vec<Polynomial<FEp>>,
+    /// u_i and w_i in evaluation form over the domain, see `u_polynomial` for the coefficients
+    pub u_polynomials: Vec<SparseEvaluations>,
+    pub w_polynomials: Vec<SparseEvaluations>,
     pub target: Polynomial<FEp>,
-    pub r1cs: R1CS,
+    pub r1cs: SparseR1CS,
 }
 
 #[derive(Debug)]
@@ -25,15 +26,163 @@ pub enum CreationError {
     PolynomialVectorsSizeMismatch,
 }
 
+/// The assignment does not satisfy the program, t(x) does not divide u(x)^2 - w(x)
+/// for a SAP or u(x) v(x) - w(x) for a QAP.
+#[derive(Debug, PartialEq, Eq)]
+pub enum QuotientError {
+    NonZeroRemainder,
+}
+
+/// A square constraint of the SAP that fails, with the values of both sides.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnsatisfiedConstraint {
+    /// Row of the SAP, the index of its domain point
+    pub row: usize,
+    pub origin: ConstraintOrigin,
+    /// (sum_j u_j s_j)^2
+    pub left: FEp,
+    /// sum_j w_j s_j
+    pub right: FEp,
+}
+
+/// Where a SAP row comes from in `r1cs_to_sap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintOrigin {
+    /// (a + b)^2 = 4 c + s' or (a - b)^2 = s' for R1CS constraint `constraint`
+    R1cs { constraint: usize, square: Square },
+    /// s_0^2 = s_0
+    One,
+    /// (s_i + s_0)^2 = 4 s_i + s'' or (s_i - s_0)^2 = s'' for public input `variable`
+    Instance { variable: usize, square: Square },
+    /// Padding up to a power of two, 0 = 0
+    Padding,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Square {
+    Sum,
+    Difference,
+}
+
+impl Display for UnsatisfiedConstraint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "SAP row {} is not satisfied ({} != {}): ", self.row, self.left, self.right)?;
+        let square = |s: &Square| match s {
+            Square::Sum => "sum",
+            Square::Difference => "difference",
+        };
+        match &self.origin {
+            ConstraintOrigin::R1cs { constraint, square: s } => write!(f, "{} square of R1CS constraint {constraint}", square(s)),
+            ConstraintOrigin::One => write!(f, "s_0 = 1"),
+            ConstraintOrigin::Instance { variable, square: s } => write!(f, "{} square of public input {variable}", square(s)),
+            ConstraintOrigin::Padding => write!(f, "padding"),
+        }
+    }
+}
+
+impl std::error::Error for UnsatisfiedConstraint {}
+
+/// Why an assignment cannot be proven for a program.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssignmentError {
+    /// The assignment has `actual` values where `expected` are needed
+    WrongLength { expected: usize, actual: usize },
+    /// A SAP row fails, see `is_satisfied`
+    Unsatisfied(UnsatisfiedConstraint),
+    /// t(x) does not divide the polynomial of the program
+    Quotient(QuotientError),
+}
+
+impl Display for AssignmentError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AssignmentError::WrongLength { expected, actual } => {
+                write!(f, "assignment has {actual} values, the program needs {expected}")
+            }
+            AssignmentError::Unsatisfied(constraint) => write!(f, "{constraint}"),
+            AssignmentError::Quotient(QuotientError::NonZeroRemainder) => write!(f, "t(x) does not divide the program polynomial"),
+        }
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
+impl From<UnsatisfiedConstraint> for AssignmentError {
+    fn from(constraint: UnsatisfiedConstraint) -> Self {
+        AssignmentError::Unsatisfied(constraint)
+    }
+}
+
+impl From<QuotientError> for AssignmentError {
+    fn from(error: QuotientError) -> Self {
+        AssignmentError::Quotient(error)
+    }
+}
+
+/// Multiplicative generator of Babybear, g * H' is disjoint from every subgroup H'.
+const COSET_OFFSET: u64 = 31;
+
+/// Polynomial of degree < N given by its values on the domain 1, w, .., w^(N-1).
+/// Only the nonzero values are kept, as (index of the domain point, value).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SparseEvaluations {
+    pub entries: Vec<(usize, FEp)>,
+}
+
+impl SparseEvaluations {
+    pub fn new(entries: Vec<(usize, FEp)>) -> Self {
+        SparseEvaluations { entries }
+    }
+
+    pub fn from_dense(values: &[FEp]) -> Self {
+        let entries = values
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v != FEp::zero())
+            .map(|(j, v)| (j, v.clone()))
+            .collect();
+        SparseEvaluations { entries }
+    }
+
+    /// All N values, zeros included.
+    pub fn to_dense(&self, n: usize) -> Vec<FEp> {
+        let mut values = vec![FEp::zero(); n];
+        self.add_scaled_to(&FEp::one(), &mut values);
+        values
+    }
+
+    /// Coefficient form with an inverse FFT over the domain of size n.
+    pub fn to_polynomial(&self, n: usize) -> Polynomial<FEp> {
+        Polynomial::interpolate_fft::<Fp>(&self.to_dense(n)).expect("domain should fit in the two-adic subgroup")
+    }
+
+    /// Value at a point x from the Lagrange basis at x, see `lagrange_basis_at`.
+    pub fn evaluate(&self, basis: &[FEp]) -> FEp {
+        self.entries.iter().fold(FEp::zero(), |acc, (j, v)| acc + v * &basis[*j])
+    }
+
+    /// values += c * self, on the dense values over the domain.
+    pub fn add_scaled_to(&self, c: &FEp, values: &mut [FEp]) {
+        for (j, v) in &self.entries {
+            values[*j] += c * v;
+        }
+    }
+}
+
+/// sum_i cs[i] * p_i(x) from the Lagrange basis at x, cs includes the leading s_0 = 1.
+pub fn evaluate_sum(polys: &[SparseEvaluations], cs: &[FEp], basis: &[FEp]) -> FEp {
+    polys.iter().zip(cs).fold(FEp::zero(), |acc, (p, c)| acc + c * p.evaluate(basis))
+}
+
 impl SquareArithmeticProgram {
     pub fn new(
         num_instance_variables: usize,
         num_r1cs_witness_variables: usize,
         num_r1cs_constraints: usize,
-        u_polynomials: Vec<Polynomial<FEp>>,
-        w_polynomials: Vec<Polynomial<FEp>>,
+        u_polynomials: Vec<SparseEvaluations>,
+        w_polynomials: Vec<SparseEvaluations>,
         target: Polynomial<FEp>,
-        r1cs: R1CS,
+        r1cs: SparseR1CS,
     ) -> Result<Self, CreationError> {
         // if u_polynomials.len() != w_polynomials.len()
         //     || num_instance_variables + r1cs.number_of_outputs > u_polynomials.len()
@@ -61,52 +210,135 @@ impl SquareArithmeticProgram {
         })
     }
 
-    pub fn h_polinomial(&self, c: &[FEp]) -> Polynomial<FEp> {
-        self.p_polinomial(c).div_with_ref(&self.target)
+    /// Size N of the evaluation domain, t(x) = x^N - 1.
+    pub fn domain_size(&self) -> usize {
+        self.target.degree()
     }
 
-    pub fn p_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
-        let u_x: Polynomial<FEp> = self.u_polynomials[0].clone()
-            + self.u_polynomials[1..]
-                .iter()
-                .zip(cs)
-                .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-                .reduce(|x, y| x + y)
-                .unwrap();
-
-        let w_x: Polynomial<FEp> = self.w_polynomials[0].clone()
-            + self.w_polynomials[1..]
-                .iter()
-                .zip(cs)
-                .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-                .reduce(|x, y| x + y)
-                .unwrap();
-
-        // let p_polinomial = u_x.clone() * u_x.clone() - w_x.clone();
-
-        // let h = p_polinomial.clone().div_with_ref(&self.target);
-
-        // // check condition
-        // let right = w_x.clone() + h.mul_with_ref(&self.target);
-        
-        // println!("\nright\n");
-        // for j in 0..right.coeff_len() {
-        //     print!("{} ", right.coefficients[j]);
-        // }
+    /// The evaluation domain 1, w, .., w^(N-1) for a primitive N-th root of unity w.
+    pub fn domain(&self) -> Vec<FEp> {
+        domain(self.domain_size())
+    }
 
-        // println!("\nleft\n");
-        // for j in 0..p_polinomial.coeff_len() {
-        //     print!("{} ", p_polinomial.coefficients[j]);
-        // }
+    /// L_j(x) for every point w^j of the domain, see `lagrange_basis`.
+    pub fn lagrange_basis_at(&self, x: &FEp) -> Vec<FEp> {
+        lagrange_basis(self.domain_size(), x)
+    }
+
+    /// u_i(x) in coefficient form.
+    pub fn u_polynomial(&self, i: usize) -> Polynomial<FEp> {
+        self.u_polynomials[i].to_polynomial(self.domain_size())
+    }
+
+    /// w_i(x) in coefficient form.
+    pub fn w_polynomial(&self, i: usize) -> Polynomial<FEp> {
+        self.w_polynomials[i].to_polynomial(self.domain_size())
+    }
+
+    /// SAP witness for the R1CS assignment (1, x, w): w followed by the extra variables
+    /// in the order `r1cs_to_sap` allocates them, (a_i - b_i)^2 for every R1CS constraint,
+    /// then (x_i - 1)^2 for every public input.
+    pub fn extend_witness(&self, r1cs_assignment: &[FEp]) -> Result<Vec<FEp>, AssignmentError> {
+        let num_instance_variables = self.num_instance_variables;
+        let expected = num_instance_variables + self.num_r1cs_witness_variables;
+        if r1cs_assignment.len() != expected {
+            return Err(AssignmentError::WrongLength { expected, actual: r1cs_assignment.len() });
+        }
+        let a_s = self.r1cs.a.mul_vector(r1cs_assignment);
+        let b_s = self.r1cs.b.mul_vector(r1cs_assignment);
+
+        let mut w = r1cs_assignment[num_instance_variables..].to_vec();
+        w.extend(a_s.iter().zip(&b_s).map(|(a_i, b_i)| {
+            let d = a_i - b_i;
+            d.clone() * d
+        }));
+        w.extend(r1cs_assignment[1..num_instance_variables].iter().map(|x| {
+            let d = x - FEp::one();
+            d.clone() * d
+        }));
+        Ok(w)
+    }
+
+    /// Checks (sum_j u_j(w^i) s_j)^2 = sum_j w_j(w^i) s_j at every point of the domain
+    /// and reports the first row that fails. `cs` is s_1, .. as in `h_polinomial`,
+    /// s_0 = 1 is implicit.
+    pub fn is_satisfied(&self, cs: &[FEp]) -> Result<(), UnsatisfiedConstraint> {
+        assert_eq!(cs.len() + 1, self.u_polynomials.len(), "assignment should cover every SAP variable");
+        let u_evals = combine(&self.u_polynomials, cs, self.domain_size());
+        let w_evals = combine(&self.w_polynomials, cs, self.domain_size());
+
+        match u_evals.iter().zip(&w_evals).position(|(u, w)| &(u * u) != w) {
+            None => Ok(()),
+            Some(row) => Err(UnsatisfiedConstraint {
+                row,
+                origin: self.row_origin(row),
+                left: &u_evals[row] * &u_evals[row],
+                right: w_evals[row].clone(),
+            }),
+        }
+    }
+
+    /// Constraint of `r1cs_to_sap` that produced SAP row `row`.
+    pub fn row_origin(&self, row: usize) -> ConstraintOrigin {
+        let square = |r: usize| if r & 1 == 0 { Square::Sum } else { Square::Difference };
+        let extra_constr_offset = 2 * self.num_r1cs_constraints;
+
+        if row < extra_constr_offset {
+            ConstraintOrigin::R1cs { constraint: row / 2, square: square(row) }
+        } else if row == extra_constr_offset {
+            ConstraintOrigin::One
+        } else if row < extra_constr_offset + 2 * self.num_instance_variables - 1 {
+            // row 2m + 2i - 1 is the sum square of input i, row 2m + 2i the difference
+            let k = row - extra_constr_offset;
+            ConstraintOrigin::Instance { variable: k.div_ceil(2), square: square(k + 1) }
+        } else {
+            ConstraintOrigin::Padding
+        }
+    }
+
+    /// u(x) = u_0(x) + sum_i c_i u_i(x) in coefficient form, one inverse FFT.
+    pub fn u_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
+        let u_evals = combine(&self.u_polynomials, cs, self.domain_size());
+        Polynomial::interpolate_fft::<Fp>(&u_evals).expect("domain should fit in the two-adic subgroup")
+    }
 
+    /// h(x) = (u(x)^2 - w(x)) / t(x) through a coset FFT, see `coset_quotient`.
+    pub fn h_polinomial(&self, cs: &[FEp]) -> Result<Polynomial<FEp>, QuotientError> {
+        let n = self.domain_size();
+        let (u_x, w_x) = self.u_w_polinomials(cs);
+
+        let p_evals = coset_evaluations(&u_x, n)
+            .iter()
+            .zip(coset_evaluations(&w_x, n))
+            .map(|(u, w)| u * u - w)
+            .collect();
+        coset_quotient(p_evals, n)
+    }
+
+    /// u(x)^2 - w(x) in coefficient form.
+    pub fn p_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
+        let (u_x, w_x) = self.u_w_polinomials(cs);
         u_x.clone() * u_x - w_x
     }
 
+    // u(x) and w(x) combined on the domain, then one inverse FFT each
+    fn u_w_polinomials(&self, cs: &[FEp]) -> (Polynomial<FEp>, Polynomial<FEp>) {
+        let w_evals = combine(&self.w_polynomials, cs, self.domain_size());
+        let w_x = Polynomial::interpolate_fft::<Fp>(&w_evals).expect("domain should fit in the two-adic subgroup");
+
+        (self.u_polinomial(cs), w_x)
+    }
+
     // pub fn ful_instnce_size() -> usize {
 
     // }
 
     pub fn r1cs_to_sap(r1cs: R1CS) -> Self {
+        Self::sparse_r1cs_to_sap(SparseR1CS::from(&r1cs))
+    }
+
+    /// SAP of an R1CS in sparse form, the work is linear in the nonzero entries of A, B, C.
+    pub fn sparse_r1cs_to_sap(r1cs: SparseR1CS) -> Self {
         let num_r1cs_constraints = r1cs.num_of_constraints();
         let num_instance_variables = r1cs.num_instance_variables();
         let num_r1cs_aux_variables = r1cs.num_r1cs_aux_variables();
@@ -116,10 +348,10 @@ impl SquareArithmeticProgram {
         let extra_constr_offset = 2 * num_r1cs_constraints;
         let extra_var_offset2 = (num_instance_variables - 1) + num_r1cs_aux_variables + num_r1cs_constraints;
 
-        let mut a = vec![vec![FEp::zero(); sap_num_var + 1]; extra_constr_offset + 2 * num_instance_variables];
-        let mut c = vec![vec![FEp::zero(); sap_num_var + 1]; extra_constr_offset + 2 * num_instance_variables];
-
-        let (a_matrix, b_matrix, c_matrix) = r1cs.constraints_to_matrix();
+        // one row per SAP variable, one column per point of the domain
+        let rq_size = 2 * num_r1cs_constraints + 2 * (num_instance_variables - 1) + 1;
+        let mut a: Vec<BTreeMap<usize, FEp>> = vec![BTreeMap::new(); sap_num_var + 1];
+        let mut c: Vec<BTreeMap<usize, FEp>> = vec![BTreeMap::new(); sap_num_var + 1];
 
         //  (\sum a_i * s_i )* (\sum b_i * s_i ) = \sum c_i*s_i
         // into two constraints
@@ -127,39 +359,39 @@ impl SquareArithmeticProgram {
         // (\sum (a_i - b_i) s_i)^2 = s'_i
         
         for i in 0..num_r1cs_constraints {
-            for j in 0..r1cs.constraints[i].a.len() {
-                a[j][2 * i] += a_matrix[i][j].clone();
-                a[j][2 * i + 1] += a_matrix[i][j].clone();
+            for (j, v) in r1cs.a.row(i) {
+                add(&mut a[j], 2 * i, v);
+                add(&mut a[j], 2 * i + 1, v);
             }
 
-            for j in 0..r1cs.constraints[i].b.len() {
-                a[j][2 * i] += b_matrix[i][j].clone();
-                a[j][2 * i + 1] = a[j][2 * i + 1].clone() - b_matrix[i][j].clone();
+            for (j, v) in r1cs.b.row(i) {
+                add(&mut a[j], 2 * i, v);
+                add(&mut a[j], 2 * i + 1, &-v);
             }
     
-            for j in 0..r1cs.constraints[i].c.len() {
-                c[j][2 * i] += times_four(&c_matrix[i][j]);
+            for (j, v) in r1cs.c.row(i) {
+                add(&mut c[j], 2 * i, &times_four(v));
             }
     
-            c[extra_var_offset + i][2 * i] += FEp::from(1);
-            c[extra_var_offset + i][2 * i + 1] += FEp::from(1);
+            add(&mut c[extra_var_offset + i], 2 * i, &FEp::from(1));
+            add(&mut c[extra_var_offset + i], 2 * i + 1, &FEp::from(1));
         }
 
         // (s_i + s_0)^2 = 4 s_i + s''_i
         // (s_i - s_0)^2 = s''_i
     
-        a[0][extra_constr_offset] = FEp::from(1);
-        c[0][extra_constr_offset] = FEp::from(1);
+        add(&mut a[0], extra_constr_offset, &FEp::from(1));
+        add(&mut c[0], extra_constr_offset, &FEp::from(1));
 
         for i in 1..num_instance_variables {
-            a[i][extra_constr_offset + 2 * i - 1] += FEp::from(1);
-            a[0][extra_constr_offset + 2 * i - 1] += FEp::from(1);
-            c[i][extra_constr_offset + 2 * i - 1] += times_four(&FEp::from(1));
-            c[extra_var_offset2 + i][extra_constr_offset + 2 * i - 1] += FEp::from(1);
-
-            a[i][extra_constr_offset + 2 * i] += FEp::from(1);
-            a[0][extra_constr_offset + 2 * i] = a[0][extra_constr_offset + 2 * i].clone() - FEp::from(1);
-            c[extra_var_offset2 + i][2 * num_r1cs_constraints + 2 * i] += FEp::from(1);
+            add(&mut a[i], extra_constr_offset + 2 * i - 1, &FEp::from(1));
+            add(&mut a[0], extra_constr_offset + 2 * i - 1, &FEp::from(1));
+            add(&mut c[i], extra_constr_offset + 2 * i - 1, &times_four(&FEp::from(1)));
+            add(&mut c[extra_var_offset2 + i], extra_constr_offset + 2 * i - 1, &FEp::from(1));
+
+            add(&mut a[i], extra_constr_offset + 2 * i, &FEp::from(1));
+            add(&mut a[0], extra_constr_offset + 2 * i, &-FEp::from(1));
+            add(&mut c[extra_var_offset2 + i], 2 * num_r1cs_constraints + 2 * i, &FEp::from(1));
         }
 
         // println!("A");
@@ -178,64 +410,18 @@ impl SquareArithmeticProgram {
         //     println!();
         // }
 
-        let rq_size = 2 * num_r1cs_constraints + 2 * (num_instance_variables - 1) + 1;
-
-        let rs: Vec<FEp> = (0..rq_size as u64)
-            .map(|i| FEp::new(i.into()))
-            .collect();
-
-        // println!("roots:");
-        // for r in &rs {
-        //     println!("{}", r);
-        // }
-
-
-        let mut us: Vec<Polynomial<FEp>> = Vec::with_capacity(sap_num_var + 1);
-        let mut ws: Vec<Polynomial<FEp>> = Vec::with_capacity(sap_num_var + 1);
-        let mut t: Polynomial<FEp> = Polynomial::new_monomial(FEp::from(1), 0);
-
-        println!("t(x) = ");
-        for r in &rs {
-            t = t * Polynomial::new(&[-r, FEp::from(1)]);
-        }
-
-        for i in 0..t.coeff_len() {
-            println!("{}", t.coefficients[i]);
-        }
-
-        for i in 0..=sap_num_var {
-            let u_ys: Vec<FEp> = a[i].clone();
-            let w_ys: Vec<FEp> = c[i].clone();
+        // the domain is padded up to a power of two N, on the padding both sides of
+        // the SAP constraint are 0 so t(x) = x^N - 1 vanishes on all of it
+        let domain_size = rq_size.next_power_of_two();
 
-            // println!("u_ys:");
-            // for i in &u_ys {
-            //     println!("{} ", i);
-            // }
+        let sparse = |row: BTreeMap<usize, FEp>| {
+            SparseEvaluations::new(row.into_iter().filter(|(_, v)| *v != FEp::zero()).collect())
+        };
+        let us: Vec<SparseEvaluations> = a.into_iter().map(sparse).collect();
+        let ws: Vec<SparseEvaluations> = c.into_iter().map(sparse).collect();
 
-            // println!("y_ys:");
-            // for i in &w_ys {
-            //     println!("{} ", i);
-            // }
+        let t = Polynomial::new_monomial(FEp::one(), domain_size) - Polynomial::new_monomial(FEp::one(), 0);
 
-            us.push(Polynomial::interpolate(&rs, &u_ys).expect("should interpolate"));
-            ws.push(Polynomial::interpolate(&rs, &w_ys).expect("should interpolate"));
-        }
-
-        println!("A(x)");
-        for u in &us {
-            for j in 0..u.coeff_len() {
-                print!("{} ", u.coefficients[j]);
-            }
-            println!("\n");
-        }
-        println!("C(x)");
-        for w in &ws {
-            for j in 0..w.coeff_len() {
-                print!("{} ", w.coefficients[j]);
-            }
-            println!("\n");
-        }
-        
         Self {
             num_instance_variables,
             num_r1cs_witness_variables: num_r1cs_aux_variables,
@@ -249,6 +435,75 @@ impl SquareArithmeticProgram {
 
 }
 
+/// The domain 1, w, .., w^(n-1) for a primitive n-th root of unity w, n a power of two.
+pub fn domain(n: usize) -> Vec<FEp> {
+    let w = Fp::get_primitive_root_of_unity(n.trailing_zeros() as u64).expect("n should be a power of two");
+    (0..n)
+        .scan(FEp::one(), |acc, _| {
+            let x = acc.clone();
+            *acc = &*acc * &w;
+            Some(x)
+        })
+        .collect()
+}
+
+/// L_j(x) = w^j (x^n - 1) / (n (x - w^j)) for every point w^j of `domain(n)`,
+/// so that p(x) = sum_j p(w^j) L_j(x) for any p of degree < n.
+pub fn lagrange_basis(n: usize, x: &FEp) -> Vec<FEp> {
+    let domain = domain(n);
+    if let Some(k) = domain.iter().position(|w| w == x) {
+        let mut basis = vec![FEp::zero(); n];
+        basis[k] = FEp::one();
+        return basis;
+    }
+
+    let mut diffs: Vec<FEp> = domain.iter().map(|w| x - w).collect();
+    FEp::inplace_batch_inverse(&mut diffs).expect("x is not in the domain");
+    let scale = (x.pow(n as u64) - FEp::one()) * FEp::from(n as u64).inv().unwrap();
+    domain.iter().zip(&diffs).map(|(w, d)| w * d * &scale).collect()
+}
+
+/// p on the coset g * H' with |H'| = 2n, enough points for products of two
+/// polynomials of degree < n.
+pub fn coset_evaluations(p: &Polynomial<FEp>, n: usize) -> Vec<FEp> {
+    Polynomial::evaluate_offset_fft::<Fp>(p, 2, Some(n), &FEp::from(COSET_OFFSET)).expect("coset should fit in the two-adic subgroup")
+}
+
+/// h = p / (x^n - 1) from the values of p on the coset of `coset_evaluations`:
+/// divided pointwise and interpolated back. When x^n - 1 divides p the quotient
+/// has degree < n - 1, a larger one means a nonzero remainder.
+pub fn coset_quotient(p_evals: Vec<FEp>, n: usize) -> Result<Polynomial<FEp>, QuotientError> {
+    let offset = FEp::from(COSET_OFFSET);
+
+    // t(g w^i) = g^n (-1)^i - 1 for w of order 2n, two values to invert
+    let g_n = offset.pow(n as u64);
+    let t_inv = [(&g_n - FEp::one()).inv().unwrap(), (-g_n - FEp::one()).inv().unwrap()];
+
+    let h_evals: Vec<FEp> = p_evals.iter().enumerate().map(|(i, p)| p * &t_inv[i & 1]).collect();
+    let h = Polynomial::interpolate_offset_fft::<Fp>(&h_evals, &offset).expect("coset should fit in the two-adic subgroup");
+
+    if h.coeff_len() >= n {
+        return Err(QuotientError::NonZeroRemainder);
+    }
+    Ok(h)
+}
+
+/// p_0 + sum_i cs[i] * p_(i+1) on the whole domain of size n.
+pub fn combine(polys: &[SparseEvaluations], cs: &[FEp], n: usize) -> Vec<FEp> {
+    let mut values = polys[0].to_dense(n);
+    for (p, c) in polys[1..].iter().zip(cs) {
+        p.add_scaled_to(c, &mut values);
+    }
+    values
+}
+
+fn add(row: &mut BTreeMap<usize, FEp>, j: usize, value: &FEp) {
+    if *value != FEp::zero() {
+        let entry = row.entry(j).or_insert_with(FEp::zero);
+        *entry = &*entry + value;
+    }
+}
+
 fn times_four(x : &FEp) -> FEp {
     let times_two = x + x;
     times_two.clone()  + times_two
@@ -268,7 +523,7 @@ fn eval(terms: &[FEp], assignment: &[FEp]) -> FEp {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::test_ex::{new_test_first_constraint, new_test_r1cs, new_test_second_constraint};
+    use crate::test_ex::{new_test_first_constraint, new_test_r1cs, new_test_sap_assignment, new_test_second_constraint};
 
     use super::*;
    
@@ -287,58 +542,115 @@ pub mod tests {
 
         let sap = SquareArithmeticProgram::r1cs_to_sap(r1cs.clone());
 
-        // instance_plus_withess
-        let mut full_input: Vec<FEp> = vec![FEp::from(1), FEp::from(3), FEp::from(5), FEp::from(4),
-                                           FEp::from(2), FEp::from(8), FEp::from(64)];
-
-        let (a, b, _c) = &r1cs.constraints_to_matrix();
-
-        // extra_var = (a_i - b_i)^2
-        let temp = a.iter()
-                                                    .zip(b.iter())
-                                                    .map(|(a_i, b_i)| {
-                                                        let mut extra_var = eval(a_i, &full_input);
-                                                        extra_var = extra_var - eval(b_i, &full_input);
-                                                        extra_var.clone() * extra_var
-                                                    })
-                                                    .collect::<Vec<_>>();
-    
-        full_input.extend(temp); 
-
-        // extra_var = (x_i - 1)^2
-        let one = FEp::from(1);
-        for i in 1..sap.num_instance_variables {
-            let mut extra_var = full_input[i].clone();
-            extra_var = extra_var - &one;
-            extra_var *= extra_var.clone();
-            full_input.push(extra_var);
+        let full_input: Vec<FEp> = vec![FEp::from(1), FEp::from(3), FEp::from(5), FEp::from(4),
+                                       FEp::from(2), FEp::from(8), FEp::from(64)];
+
+        let x = full_input[1..sap.num_instance_variables].to_vec();
+        let w = sap.extend_witness(&full_input).unwrap();
+        assert_eq!(w.len() + x.len() + 1, sap.u_polynomials.len());
+        assert_eq!(sap.is_satisfied(&[x, w].concat()), Ok(()));
+    }
+
+    #[test]
+    fn sparse_and_dense_r1cs_give_the_same_sap() {
+        let r1cs = new_test_r1cs();
+        let sparse = SparseR1CS::from(&r1cs);
+
+        let sap = SquareArithmeticProgram::sparse_r1cs_to_sap(sparse.clone());
+        assert_eq!(sap, SquareArithmeticProgram::r1cs_to_sap(r1cs));
+        assert_eq!(sap.r1cs, sparse);
+    }
+
+    #[test]
+    fn sap_lives_on_power_of_two_subgroup() {
+        let sap = SquareArithmeticProgram::r1cs_to_sap(new_test_r1cs());
+        let domain = sap.domain();
+        let n = sap.domain_size();
+
+        assert!(n.is_power_of_two());
+        assert!(n > 2 * sap.num_r1cs_constraints + 2 * (sap.num_instance_variables - 1));
+        assert!(domain.iter().all(|x| sap.target.evaluate(x) == FEp::zero()));
+        assert!((0..sap.u_polynomials.len()).all(|i| sap.u_polynomial(i).coeff_len() <= n && sap.w_polynomial(i).coeff_len() <= n));
+
+        // the constraint 2 * i of the R1CS sits at w^(2 i): u_0 is the coefficient of s_0 = 1 in a + b
+        let (a, b) = (sap.r1cs.a.to_dense(), sap.r1cs.b.to_dense());
+        assert_eq!(sap.u_polynomial(0).evaluate(&domain[2]), &a[1][0] + &b[1][0]);
+
+        let (x, w) = new_test_sap_assignment();
+        let (_, remainder) = sap.p_polinomial(&[x, w].concat()).long_division_with_remainder(&sap.target);
+        assert_eq!(remainder, Polynomial::zero());
+    }
+
+    #[test]
+    fn coset_quotient_matches_division() {
+        let sap = SquareArithmeticProgram::r1cs_to_sap(new_test_r1cs());
+        let (x, w) = new_test_sap_assignment();
+        let full = [x, w].concat();
+
+        let h = sap.h_polinomial(&full).unwrap();
+        assert_eq!(h.mul_with_ref(&sap.target), sap.p_polinomial(&full));
+        assert_eq!(h, sap.p_polinomial(&full).div_with_ref(&sap.target));
+    }
+
+    #[test]
+    fn sparse_evaluations_match_coefficient_form() {
+        let sap = SquareArithmeticProgram::r1cs_to_sap(new_test_r1cs());
+        let n = sap.domain_size();
+
+        for x in [FEp::from(123456), sap.domain()[3].clone()] {
+            let basis = sap.lagrange_basis_at(&x);
+            for i in 0..sap.u_polynomials.len() {
+                assert!(sap.u_polynomials[i].entries.len() < n);
+                assert_eq!(sap.u_polynomials[i].evaluate(&basis), sap.u_polynomial(i).evaluate(&x));
+                assert_eq!(sap.w_polynomials[i].evaluate(&basis), sap.w_polynomial(i).evaluate(&x));
+            }
         }
 
+        let dense = sap.w_polynomials[1].to_dense(n);
+        assert_eq!(SparseEvaluations::from_dense(&dense), sap.w_polynomials[1]);
+    }
 
-        println!("Full instance"); 
-        for i in &full_input {
-            println!("{}", i);
-        }  
-        
-        sap.p_polinomial(&full_input);
-        // assert_eq!(double_u_x, right);
+    #[test]
+    fn satisfied_assignment_passes_check() {
+        let sap = SquareArithmeticProgram::r1cs_to_sap(new_test_r1cs());
+        let (x, w) = new_test_sap_assignment();
 
-        // let zero = FEp::from(0);
-        // let rq_size = 2 * sap.num_r1cs_constraints + 2 * (sap.num_instance_variables - 1) + 1;
+        assert_eq!(sap.is_satisfied(&[x, w].concat()), Ok(()));
+    }
 
-        // let mut a = vec![zero; rq_size];
-        // for i in 0..sap.num_r1cs_constraints {
-        //     a[2*i] += eval(&sap.r1cs.constraints[i].a, &full_input);
-        //     a[2*i] += eval(&sap.r1cs.constraints[i].b, &full_input);
+    #[test]
+    fn unsatisfied_rows_point_to_r1cs_constraints() {
+        let sap = SquareArithmeticProgram::r1cs_to_sap(new_test_r1cs());
+        let (x, w) = new_test_sap_assignment();
+        let m = sap.num_r1cs_constraints;
+
+        // the first extra variable s' only appears in the two squares of constraint 0
+        let mut bad = [x.clone(), w.clone()].concat();
+        let extra = sap.num_instance_variables - 1 + sap.num_r1cs_witness_variables;
+        bad[extra] += FEp::one();
+        let err = sap.is_satisfied(&bad).unwrap_err();
+        assert_eq!((err.row, err.origin), (0, ConstraintOrigin::R1cs { constraint: 0, square: Square::Sum }));
+        assert!(err.to_string().contains("R1CS constraint 0"));
+
+        // the last extra variable s'' only appears in the squares of the last public input
+        let mut bad = [x, w].concat();
+        *bad.last_mut().unwrap() += FEp::one();
+        let err = sap.is_satisfied(&bad).unwrap_err();
+        let variable = sap.num_instance_variables - 1;
+        assert_eq!(err.row, 2 * m + 2 * variable - 1);
+        assert_eq!(err.origin, ConstraintOrigin::Instance { variable, square: Square::Sum });
+        assert_eq!(sap.row_origin(2 * m + 2 * variable), ConstraintOrigin::Instance { variable, square: Square::Difference });
+        assert_eq!(sap.row_origin(2 * m), ConstraintOrigin::One);
+        assert_eq!(sap.row_origin(sap.domain_size() - 1), ConstraintOrigin::Padding);
+    }
 
-        //     a[2*i + 1] += eval(&sap.r1cs.constraints[i].a, &full_input);
-        //     a[2*i + 1] = a[2*i + 1] -  eval(&sap.r1cs.constraints[i].b, &full_input);
-        // }
+    #[test]
+    fn quotient_rejects_unsatisfied_assignment() {
+        let sap = SquareArithmeticProgram::r1cs_to_sap(new_test_r1cs());
+        let (x, mut w) = new_test_sap_assignment();
+        w[0] += FEp::one();
 
-        // println!("\na[]");
-        // for i in a {
-        //     println!("{}", i);
-        // }
+        assert_eq!(sap.h_polinomial(&[x, w].concat()), Err(QuotientError::NonZeroRemainder));
     }
 
     #[allow(dead_code)]
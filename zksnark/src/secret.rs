@@ -0,0 +1,215 @@
+use crate::{
+    common::*,
+    gaussian::{sample_uniform_zq, DiscreteGaussian, SamplingMode},
+    params::LweParams,
+};
+use rand::{CryptoRng, Rng, RngCore};
+
+/// Distribution of the LWE secret, part of the parameter set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecretDistribution {
+    /// Uniform mod q
+    Uniform,
+    /// Uniform over {-1, 0, 1}
+    Ternary,
+    /// Uniform over {0, 1}
+    Binary,
+    /// Exactly `weight` nonzero coordinates, each -1 or 1
+    SparseTernary { weight: usize },
+    /// Same discrete Gaussian as the error, normal form LWE
+    Gaussian,
+}
+
+impl SecretDistribution {
+    /// Samples a secret of dimension n, `gaussian` is the error sampler of the scheme.
+    /// In `SamplingMode::ConstantTime` small secrets are drawn without rejection and
+    /// without memory accesses at secret positions.
+    pub fn sample<R: RngCore + CryptoRng>(&self, n: usize, gaussian: &DiscreteGaussian, rng: &mut R) -> Vec<FE> {
+        match (self, gaussian.mode()) {
+            (SecretDistribution::Uniform, mode) => (0..n).map(|_| sample_uniform_zq(mode, rng)).collect(),
+            (SecretDistribution::Ternary, SamplingMode::VariableTime) => {
+                (0..n).map(|_| fe_from_i64(rng.gen_range(-1..=1))).collect()
+            }
+            (SecretDistribution::Ternary, SamplingMode::ConstantTime) => {
+                (0..n).map(|_| fe_from_i64(ternary_ct(rng.next_u64()))).collect()
+            }
+            (SecretDistribution::Binary, SamplingMode::VariableTime) => (0..n).map(|_| FE::from(rng.gen_range(0..=1))).collect(),
+            (SecretDistribution::Binary, SamplingMode::ConstantTime) => (0..n).map(|_| FE::from(rng.next_u64() & 1)).collect(),
+            (SecretDistribution::SparseTernary { weight }, SamplingMode::VariableTime) => {
+                // partial Fisher-Yates, the first `weight` positions of the permutation are nonzero
+                let mut positions: Vec<usize> = (0..n).collect();
+                let mut s = vec![FE::from(0); n];
+                for i in 0..*weight {
+                    positions.swap(i, rng.gen_range(i..n));
+                    s[positions[i]] = if rng.gen::<bool>() { FE::from(1) } else { -FE::from(1) };
+                }
+                s
+            }
+            (SecretDistribution::SparseTernary { weight }, SamplingMode::ConstantTime) => {
+                // `weight` random signs followed by zeros, shuffled by sorting on random keys
+                let mut entries: Vec<(u64, i64)> = (0..n)
+                    .map(|i| {
+                        let sign = 1 - 2 * (rng.next_u64() & 1) as i64;
+                        (rng.next_u64(), sign * (i < *weight) as i64)
+                    })
+                    .collect();
+                sort_by_key_ct(&mut entries);
+                entries.iter().map(|&(_, x)| fe_from_i64(x)).collect()
+            }
+            (SecretDistribution::Gaussian, _) => (0..n).map(|_| gaussian.sample_fe(rng)).collect(),
+        }
+    }
+
+    /// Entropy per coordinate in bits, as used by the hybrid attack.
+    pub fn entropy_bits(&self, params: &LweParams) -> f64 {
+        let n = params.n() as f64;
+        match self {
+            SecretDistribution::Uniform => (params.q() as f64).log2(),
+            SecretDistribution::Ternary => 3f64.log2(),
+            SecretDistribution::Binary => 1.0,
+            SecretDistribution::SparseTernary { weight } => {
+                // log2(binomial(n, h)) + h sign bits, spread over n coordinates
+                let h = *weight as f64;
+                let log_binomial: f64 = (0..*weight).map(|i| ((n - i as f64) / (h - i as f64)).log2()).sum();
+                (log_binomial + h) / n
+            }
+            SecretDistribution::Gaussian => (params.std_dev() * (2.0 * std::f64::consts::PI * std::f64::consts::E).sqrt()).log2(),
+        }
+    }
+
+    /// Standard deviation of a coordinate. A uniform secret counts as having the
+    /// error deviation, an attacker can always switch to normal form.
+    pub fn std_dev(&self, params: &LweParams) -> f64 {
+        match self {
+            SecretDistribution::Uniform | SecretDistribution::Gaussian => params.std_dev(),
+            SecretDistribution::Ternary => (2f64 / 3.0).sqrt(),
+            SecretDistribution::Binary => 0.5,
+            SecretDistribution::SparseTernary { weight } => (*weight as f64 / params.n() as f64).sqrt(),
+        }
+    }
+
+    /// Bound on sum_i |s_i| over the centered representatives.
+    pub fn l1_bound(&self, params: &LweParams) -> u128 {
+        let n = params.n() as u128;
+        match self {
+            SecretDistribution::Uniform => n * (params.q() / 2) as u128,
+            SecretDistribution::Ternary | SecretDistribution::Binary => n,
+            SecretDistribution::SparseTernary { weight } => *weight as u128,
+            SecretDistribution::Gaussian => n * params.max_error() as u128,
+        }
+    }
+}
+
+// -1, 0 or 1 from 64 random bits as floor(3 r / 2^64) - 1, off uniform by about 2^-64
+fn ternary_ct(r: u64) -> i64 {
+    ((3 * r as u128) >> 64) as i64 - 1
+}
+
+// Knuth's merge exchange sort (TAOCP 5.2.2, algorithm M). Which pairs get compared
+// depends only on the length, and every exchange is a masked swap.
+fn sort_by_key_ct(v: &mut [(u64, i64)]) {
+    let n = v.len();
+    if n < 2 {
+        return;
+    }
+    let t = usize::BITS - (n - 1).leading_zeros();
+    let mut p = 1 << (t - 1);
+    while p > 0 {
+        let (mut q, mut r, mut d) = (1 << (t - 1), 0, p);
+        loop {
+            for i in 0..n - d {
+                if i & p == r {
+                    compare_exchange_ct(v, i, i + d);
+                }
+            }
+            if q == p {
+                break;
+            }
+            (d, q, r) = (q - p, q >> 1, p);
+        }
+        p >>= 1;
+    }
+}
+
+// puts the smaller key at i and the larger at j without branching on the keys
+fn compare_exchange_ct(v: &mut [(u64, i64)], i: usize, j: usize) {
+    let (a, b) = (v[i], v[j]);
+    // all ones when b.0 < a.0
+    let mask = 0u64.wrapping_sub(b.0.overflowing_sub(a.0).1 as u64);
+    let key = (a.0 ^ b.0) & mask;
+    let value = (a.1 ^ b.1) & mask as i64;
+    v[i] = (a.0 ^ key, a.1 ^ value);
+    v[j] = (b.0 ^ key, b.1 ^ value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{modswitch::secret_l1_norm, noise::centered};
+
+    const MODES: [SamplingMode; 2] = [SamplingMode::VariableTime, SamplingMode::ConstantTime];
+
+    fn sample(secret: SecretDistribution, n: usize, mode: SamplingMode) -> Vec<i128> {
+        let params = LweParams::toy();
+        let gaussian = DiscreteGaussian::new_with_mode(params.std_dev(), mode);
+        secret.sample(n, &gaussian, &mut seeded_rng(81)).iter().map(centered).collect()
+    }
+
+    #[test]
+    fn small_secrets_stay_in_range() {
+        for mode in MODES {
+            let ternary = sample(SecretDistribution::Ternary, 300, mode);
+            assert!(ternary.iter().all(|x| (-1..=1).contains(x)));
+            assert!((-1..=1).all(|x| ternary.contains(&x)));
+            let binary = sample(SecretDistribution::Binary, 300, mode);
+            assert!(binary.iter().all(|x| (0..=1).contains(x)));
+            assert!(binary.contains(&0) && binary.contains(&1));
+        }
+    }
+
+    #[test]
+    fn sparse_ternary_has_exact_weight() {
+        for mode in MODES {
+            let s = sample(SecretDistribution::SparseTernary { weight: 17 }, 256, mode);
+            assert_eq!(s.iter().filter(|x| **x != 0).count(), 17);
+            assert!(s.iter().all(|x| (-1..=1).contains(x)));
+            assert!(s.contains(&-1) && s.contains(&1));
+            // the nonzero coordinates are spread, not left in front
+            assert!(s[17..].iter().any(|x| *x != 0));
+        }
+    }
+
+    #[test]
+    fn ternary_ct_splits_the_range_in_thirds() {
+        assert_eq!(ternary_ct(0), -1);
+        assert_eq!(ternary_ct(u64::MAX / 3), -1);
+        assert_eq!(ternary_ct(u64::MAX / 3 + 1), 0);
+        assert_eq!(ternary_ct(u64::MAX), 1);
+    }
+
+    #[test]
+    fn constant_time_sort_matches_sort() {
+        let mut rng = seeded_rng(83);
+        for n in 0..70 {
+            let mut v: Vec<(u64, i64)> = (0..n).map(|i| (rng.gen_range(0..50), i as i64)).collect();
+            let mut expected = v.clone();
+            expected.sort_by_key(|x| x.0);
+            sort_by_key_ct(&mut v);
+            let keys = |v: &[(u64, i64)]| v.iter().map(|x| x.0).collect::<Vec<_>>();
+            assert_eq!(keys(&v), keys(&expected), "n = {}", n);
+            let mut values: Vec<i64> = v.iter().map(|x| x.1).collect();
+            values.sort();
+            assert_eq!(values, (0..n as i64).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn l1_bound_holds() {
+        let params = LweParams::toy();
+        let gaussian = DiscreteGaussian::new(params.std_dev());
+        for secret in [SecretDistribution::Ternary, SecretDistribution::SparseTernary { weight: 3 }, SecretDistribution::Gaussian] {
+            let s = secret.sample(params.n(), &gaussian, &mut seeded_rng(82));
+            assert!(secret_l1_norm(&s) <= secret.l1_bound(&params));
+        }
+    }
+}
@@ -1,66 +1,413 @@
-use crate::{common::*, sap::SquareArithmeticProgram as SAP,  lwe::*};
+use crate::{common::*, flooding::{floods_within_budget, zero_samples, zero_sum_budget}, gadget::GadgetVector, keyswitch::KeySwitchKey, qap::QuadraticArithmeticProgram as QAP, sap::SquareArithmeticProgram as SAP, ssp::SquareSpanProgram as SSP, packed::*, gaussian::SamplingMode, noise::NoiseBudget, params::{LweParams, ParamsError}, prover::{proof_noise_budget, qap_proof_noise_budget, ssp_proof_noise_budget}};
+use rand::{CryptoRng, RngCore};
 
+/// Number of CRS messages sharing one `a` vector in `setup`. Packing k messages
+/// shrinks the CRS by about a factor k but every proof element becomes an encoding
+/// of dimension k * n, so plain encodings keep the proof small by default.
+pub const DEFAULT_PACKING: usize = 1;
+
+/// Vector entries are packed `packing` per encoding under the secret S, every entry
+/// and every proof element is a scalar encoding of dimension packing * n under `flatten(S)`.
+/// Entries the prover scales by field elements are stored as gadget vectors.
 pub struct CommonReferenceString {
     pub sap: SAP,
-    pub pk: PK,
-    pub delta_t_s_2: FE,
-    pub beta_t_sk: FE,
-    pub delta_si: Vec<FE>,
-    pub delta_si_t_sk: Vec<FE>,
-    pub delta_wi_beta_vi: Vec<FE>,
+    pub pk: LweParams,
+    pub packing: usize,
+    /// delta * t(s)^2
+    pub delta_t_s_2: GadgetVector,
+    /// beta * t(s)
+    pub beta_t_sk: GadgetVector,
+    /// delta * s^i for i in 0..=deg t
+    pub delta_si: GadgetVector,
+    /// delta * s^i * t(s) for i in 0..deg t
+    pub delta_si_t_sk: GadgetVector,
+    /// delta * w_i(s) + beta * u_i(s) for every witness variable i
+    pub delta_wi_beta_vi: GadgetVector,
+    /// encodings of zero, `zero_samples` per secret block, used to rerandomize proofs
+    pub zeros: PackedVector,
+    /// proof noise is flooded up to statistical distance 2^-flooding_bits, chosen at setup
+    pub flooding_bits: u32,
+    /// bound on the noise of every entry, fresh after setup and grown by key rotation
+    pub noise: NoiseBudget,
 }
 
-pub fn setup(sap: &SAP) -> (Vec<FE>, CommonReferenceString, Vec<FEp>) {
-    let delta = sample_fr_elem_zp();
-    let beta= sample_fr_elem_zp();
-    let s = sample_fr_elem_zp();
+impl CommonReferenceString {
+    /// Dimension of the scalar encodings taken from the CRS.
+    pub fn dimension(&self) -> usize {
+        self.packing * self.pk.n()
+    }
+}
 
-    let td = vec![beta.clone(), delta.clone(), s.clone()];
+/// Proofs made with the CRS flood their noise up to statistical distance 2^-flooding_bits,
+/// see `flooding::flooding_bound`. Fails with `NoiseTooLarge` when flooded honest proofs
+/// for this circuit would not decode under `pk`.
+pub fn setup<R: RngCore + CryptoRng>(
+    sap: &SAP,
+    pk: &LweParams,
+    flooding_bits: u32,
+    rng: &mut R,
+) -> Result<(Vec<FE>, CommonReferenceString, Vec<FEp>), ParamsError> {
+    setup_with_packing(sap, pk, DEFAULT_PACKING, flooding_bits, rng)
+}
+
+/// Same as `setup` with `packing` messages per encoding, 1 gives plain LWE encodings.
+pub fn setup_with_packing<R: RngCore + CryptoRng>(
+    sap: &SAP,
+    pk: &LweParams,
+    packing: usize,
+    flooding_bits: u32,
+    rng: &mut R,
+) -> Result<(Vec<FE>, CommonReferenceString, Vec<FEp>), ParamsError> {
+    let delta = sample_fr_elem_zp(rng);
+    let beta= sample_fr_elem_zp(rng);
+    let s = sample_fr_elem_zp(rng);
 
-    let pk =  PK{n: 5, p: 7, q: 218, alfa: 0.29};
-    let lwe: LWE = LWE::new(5, 2013265921, 18446744069414584321, 0.000000000000001);
-    let sk = lwe.key_gen();
+    let td = vec![beta.clone(), delta.clone(), s.clone()];
 
-    let vrs = sk.clone();
+    let lwe = PackedLwe::new_with_mode(*pk, packing, SamplingMode::ConstantTime);
+    let sk = lwe.key_gen(rng);
 
-    println!("s = {}, \ntarget(s) = {}", s.clone(), FEp::from(sap.target.evaluate(&s)));
-    // let _:() = sap.target.evaluate(&s);
+    let vrs = flatten(&sk);
 
     let t_s = FEp::from(sap.target.evaluate(&s));
 
     let temp: FEp = delta.clone() * t_s.clone() * t_s.clone();
-    let delta_t_s_2 = lwe.encode(temp, &sk);
-
-    let beta_t_sk  = lwe.encode(beta.clone() * t_s.clone(), &sk);
+    let delta_t_s_2 = GadgetVector::encode(&lwe, &[temp], &sk, rng);
+    let beta_t_sk = GadgetVector::encode(&lwe, &[beta.clone() * t_s.clone()], &sk, rng);
 
+    let mut delta_si: Vec<FEp> = Vec::with_capacity(sap.target.degree() + 1);
+    for i in 0..=sap.target.degree() {
+        delta_si.push(delta.clone() * pow(&s, i));
+    }
+    let delta_si = GadgetVector::encode(&lwe, &delta_si, &sk, rng);
 
-    let mut delta_si: Vec<FE> = Vec::with_capacity(sap.target.degree() + 1);
+    let mut delta_si_t_sk: Vec<FEp> = Vec::with_capacity(sap.target.degree());
     for i in 0..sap.target.degree() {
-        delta_si.push(lwe.encode(delta.clone() * pow(&s, i), &sk).1);
+        delta_si_t_sk.push(delta.clone() * pow(&s, i) * t_s.clone());
     }
+    let delta_si_t_sk = GadgetVector::encode(&lwe, &delta_si_t_sk, &sk, rng);
 
-    let mut delta_si_t_sk: Vec<FE> = Vec::with_capacity(sap.target.degree());
-    for i in 0..sap.target.degree() - 1 {
-        delta_si_t_sk.push(lwe.encode(delta.clone() * pow(&s, i) * t_s.clone(), &sk).1);
+    let basis = sap.lagrange_basis_at(&s);
+    let mut delta_wi_beta_vi: Vec<FEp> = Vec::with_capacity(sap.u_polynomials.len() - sap.num_instance_variables);
+    for i in sap.num_instance_variables..sap.u_polynomials.len() {
+        let temp: FEp = delta.clone() * sap.w_polynomials[i].evaluate(&basis) + beta.clone() * sap.u_polynomials[i].evaluate(&basis);
+        delta_wi_beta_vi.push(temp);
     }
+    let delta_wi_beta_vi = GadgetVector::encode(&lwe, &delta_wi_beta_vi, &sk, rng);
 
-    let delta_wi_beta_vi: Vec<FE> = Vec::with_capacity(sap.num_r1cs_witness_variables + 1);
-    for i in (sap.num_instance_variables - 1)..sap.target.degree(){
-        let temp: FEp = delta.clone() * sap.w_polynomials[i].evaluate(&s) + beta.clone() * sap.u_polynomials[i].evaluate(&s);
-        delta_si_t_sk.push(lwe.encode(temp, &sk).1);
-    }
+    let zeros = zero_samples(pk, flooding_bits) * packing;
 
-    let crs = CommonReferenceString{
+    let mut crs = CommonReferenceString{
         sap: sap.clone(), 
-        pk: pk, 
-        delta_t_s_2: delta_t_s_2.1,
-        beta_t_sk: beta_t_sk.1,
-        delta_si: delta_si,
-        delta_si_t_sk: delta_si_t_sk,
-        delta_wi_beta_vi: delta_wi_beta_vi,
+        pk: *pk, 
+        packing,
+        delta_t_s_2,
+        beta_t_sk,
+        delta_si,
+        delta_si_t_sk,
+        delta_wi_beta_vi,
+        zeros: lwe.encode_vector(&[], &sk, rng),
+        flooding_bits,
+        noise: NoiseBudget::fresh(pk),
+    };
+
+    // the encodings of zero are most of the CRS, check the budget before making them
+    if !sap_proofs_decode(&crs, zeros) {
+        return Err(ParamsError::NoiseTooLarge);
+    }
+    crs.zeros = lwe.encode_vector(&vec![FEp::from(0); zeros], &sk, rng);
+
+    Ok((vrs, crs, td))
+}
+
+/// CRS of the QAP variant, packed as `CommonReferenceString`. The proof is
+/// A = alpha + u(s) + r delta, B = beta + v(s) + r' delta and
+/// C = (sum_wit s_i (beta u_i(s) + alpha v_i(s) + w_i(s)) + h(s) t(s)) / delta + r' A + r B - r r' delta.
+pub struct QapCommonReferenceString {
+    pub qap: QAP,
+    pub pk: LweParams,
+    pub packing: usize,
+    /// alpha, beta and delta
+    pub scalars: GadgetVector,
+    /// s^i for i in 0..deg t
+    pub si: GadgetVector,
+    /// s^i * t(s) / delta for i in 0..deg t - 1
+    pub si_t_delta: GadgetVector,
+    /// (beta * u_i(s) + alpha * v_i(s) + w_i(s)) / delta for every witness variable i
+    pub witness_terms: GadgetVector,
+    /// encodings of zero, `zero_samples` per secret block, used to rerandomize proofs
+    pub zeros: PackedVector,
+    /// proof noise is flooded up to statistical distance 2^-flooding_bits, chosen at setup
+    pub flooding_bits: u32,
+    /// bound on the noise of every entry, fresh after setup and grown by key rotation
+    pub noise: NoiseBudget,
+}
+
+impl QapCommonReferenceString {
+    /// Dimension of the scalar encodings taken from the CRS.
+    pub fn dimension(&self) -> usize {
+        self.packing * self.pk.n()
+    }
+}
+
+/// Setup of the QAP variant, the trapdoor is `td = [alpha, beta, delta, s]`.
+pub fn setup_qap<R: RngCore + CryptoRng>(
+    qap: &QAP,
+    pk: &LweParams,
+    flooding_bits: u32,
+    rng: &mut R,
+) -> Result<(Vec<FE>, QapCommonReferenceString, Vec<FEp>), ParamsError> {
+    setup_qap_with_packing(qap, pk, DEFAULT_PACKING, flooding_bits, rng)
+}
+
+/// Same as `setup_qap` with `packing` messages per encoding.
+pub fn setup_qap_with_packing<R: RngCore + CryptoRng>(
+    qap: &QAP,
+    pk: &LweParams,
+    packing: usize,
+    flooding_bits: u32,
+    rng: &mut R,
+) -> Result<(Vec<FE>, QapCommonReferenceString, Vec<FEp>), ParamsError> {
+    let alpha = sample_fr_elem_zp(rng);
+    let beta = sample_fr_elem_zp(rng);
+    let delta = sample_fr_elem_zp(rng);
+    let s = sample_fr_elem_zp(rng);
+
+    let td = vec![alpha.clone(), beta.clone(), delta.clone(), s.clone()];
+
+    let lwe = PackedLwe::new_with_mode(*pk, packing, SamplingMode::ConstantTime);
+    let sk = lwe.key_gen(rng);
+
+    let degree = qap.target.degree();
+    let delta_inv = delta.inv().unwrap();
+    let t_s_delta = qap.target.evaluate(&s) * &delta_inv;
+
+    let si: Vec<FEp> = (0..degree).map(|i| pow(&s, i)).collect();
+    let si_t_delta: Vec<FEp> = si[..degree - 1].iter().map(|x| x * &t_s_delta).collect();
+
+    let basis = qap.lagrange_basis_at(&s);
+    let witness_terms: Vec<FEp> = (qap.num_instance_variables..qap.u_polynomials.len())
+        .map(|i| {
+            (&beta * qap.u_polynomials[i].evaluate(&basis)
+                + &alpha * qap.v_polynomials[i].evaluate(&basis)
+                + qap.w_polynomials[i].evaluate(&basis))
+                * &delta_inv
+        })
+        .collect();
+
+    let mut crs = QapCommonReferenceString {
+        qap: qap.clone(),
+        pk: *pk,
+        packing,
+        scalars: GadgetVector::encode(&lwe, &[alpha, beta, delta], &sk, rng),
+        si: GadgetVector::encode(&lwe, &si, &sk, rng),
+        si_t_delta: GadgetVector::encode(&lwe, &si_t_delta, &sk, rng),
+        witness_terms: GadgetVector::encode(&lwe, &witness_terms, &sk, rng),
+        zeros: lwe.encode_vector(&[], &sk, rng),
+        flooding_bits,
+        noise: NoiseBudget::fresh(pk),
+    };
+
+    let zeros = zero_samples(pk, flooding_bits) * packing;
+    if !qap_proofs_decode(&crs, zeros) {
+        return Err(ParamsError::NoiseTooLarge);
+    }
+    crs.zeros = lwe.encode_vector(&vec![FEp::from(0); zeros], &sk, rng);
+
+    Ok((flatten(&sk), crs, td))
+}
+
+/// CRS of the SSP variant for boolean circuits, packed as `CommonReferenceString`.
+/// The proof is H = h'(s), V = v_wit(s) + d t(s) and B = beta * V for a prover chosen d.
+pub struct SspCommonReferenceString {
+    pub ssp: SSP,
+    pub pk: LweParams,
+    pub packing: usize,
+    /// t(s) and beta * t(s)
+    pub scalars: GadgetVector,
+    /// s^i for i in 0..=deg t
+    pub si: GadgetVector,
+    /// v_i(s) for every witness wire i
+    pub vi: GadgetVector,
+    /// beta * v_i(s) for every witness wire i
+    pub beta_vi: GadgetVector,
+    /// encodings of zero, `zero_samples` per secret block, used to rerandomize proofs
+    pub zeros: PackedVector,
+    /// proof noise is flooded up to statistical distance 2^-flooding_bits, chosen at setup
+    pub flooding_bits: u32,
+    /// bound on the noise of every entry, fresh after setup and grown by key rotation
+    pub noise: NoiseBudget,
+}
+
+impl SspCommonReferenceString {
+    /// Dimension of the scalar encodings taken from the CRS.
+    pub fn dimension(&self) -> usize {
+        self.packing * self.pk.n()
+    }
+}
+
+/// Setup of the SSP variant, the trapdoor is `td = [beta, s]`.
+pub fn setup_ssp<R: RngCore + CryptoRng>(
+    ssp: &SSP,
+    pk: &LweParams,
+    flooding_bits: u32,
+    rng: &mut R,
+) -> Result<(Vec<FE>, SspCommonReferenceString, Vec<FEp>), ParamsError> {
+    setup_ssp_with_packing(ssp, pk, DEFAULT_PACKING, flooding_bits, rng)
+}
+
+/// Same as `setup_ssp` with `packing` messages per encoding.
+pub fn setup_ssp_with_packing<R: RngCore + CryptoRng>(
+    ssp: &SSP,
+    pk: &LweParams,
+    packing: usize,
+    flooding_bits: u32,
+    rng: &mut R,
+) -> Result<(Vec<FE>, SspCommonReferenceString, Vec<FEp>), ParamsError> {
+    let beta = sample_fr_elem_zp(rng);
+    let s = sample_fr_elem_zp(rng);
+
+    let td = vec![beta.clone(), s.clone()];
+
+    let lwe = PackedLwe::new_with_mode(*pk, packing, SamplingMode::ConstantTime);
+    let sk = lwe.key_gen(rng);
+
+    let t_s = ssp.target.evaluate(&s);
+
+    let si: Vec<FEp> = (0..=ssp.target.degree()).map(|i| pow(&s, i)).collect();
+
+    let basis = ssp.lagrange_basis_at(&s);
+    let vi: Vec<FEp> = ssp.v_polynomials[ssp.num_instance_variables..].iter().map(|v| v.evaluate(&basis)).collect();
+    let beta_vi: Vec<FEp> = vi.iter().map(|v| &beta * v).collect();
+
+    let mut crs = SspCommonReferenceString {
+        ssp: ssp.clone(),
+        pk: *pk,
+        packing,
+        scalars: GadgetVector::encode(&lwe, &[t_s.clone(), &beta * &t_s], &sk, rng),
+        si: GadgetVector::encode(&lwe, &si, &sk, rng),
+        vi: GadgetVector::encode(&lwe, &vi, &sk, rng),
+        beta_vi: GadgetVector::encode(&lwe, &beta_vi, &sk, rng),
+        zeros: lwe.encode_vector(&[], &sk, rng),
+        flooding_bits,
+        noise: NoiseBudget::fresh(pk),
     };
 
-    (vrs, crs, td)
+    let zeros = zero_samples(pk, flooding_bits) * packing;
+    if !ssp_proofs_decode(&crs, zeros) {
+        return Err(ParamsError::NoiseTooLarge);
+    }
+    crs.zeros = lwe.encode_vector(&vec![FEp::from(0); zeros], &sk, rng);
+
+    Ok((flatten(&sk), crs, td))
+}
+
+// whether honest proofs, rerandomized with `zeros` encodings of zero and flooded up to
+// 2^-crs.flooding_bits, still decode
+fn sap_proofs_decode(crs: &CommonReferenceString, zeros: usize) -> bool {
+    let (a_budget, b_budget) = proof_noise_budget(crs);
+    floods_within_budget(&[a_budget, b_budget], &crs.pk, &zero_sum_budget(&crs.noise, zeros), crs.flooding_bits)
+}
+
+// same for the QAP variant
+fn qap_proofs_decode(crs: &QapCommonReferenceString, zeros: usize) -> bool {
+    let (a_budget, b_budget, c_budget) = qap_proof_noise_budget(crs);
+    floods_within_budget(&[a_budget, b_budget, c_budget], &crs.pk, &zero_sum_budget(&crs.noise, zeros), crs.flooding_bits)
+}
+
+// same for the SSP variant
+fn ssp_proofs_decode(crs: &SspCommonReferenceString, zeros: usize) -> bool {
+    let (h_budget, v_budget) = ssp_proof_noise_budget(crs);
+    floods_within_budget(&[h_budget, v_budget], &crs.pk, &zero_sum_budget(&crs.noise, zeros), crs.flooding_bits)
+}
+
+// fresh secret S' with the shape of `vrs` and the key switching key from `vrs` to it
+fn rotation_key<R: RngCore + CryptoRng>(pk: &LweParams, packing: usize, vrs: &[FE], rng: &mut R) -> (KeySwitchKey, Vec<Vec<FE>>) {
+    let lwe = PackedLwe::new_with_mode(*pk, packing, SamplingMode::ConstantTime);
+    let old: Vec<Vec<FE>> = vrs.chunks(pk.n()).map(|row| row.to_vec()).collect();
+    let new = lwe.key_gen(rng);
+    (KeySwitchKey::new(&lwe, &old, &new, rng), new)
+}
+
+/// Rotates the verifier secret without a new setup: draws a fresh S', switches every
+/// CRS entry from the current secret `vrs` to it and returns the new `vrs`. Decoded
+/// values and the trapdoor `td` stay the same, so rotation only stops the old secret
+/// from decoding the new CRS and the proofs made with it. Whoever holds the old secret
+/// and a copy of the old CRS can still decode `td`, a leaked secret needs a new setup.
+/// Key switching grows the noise of every entry, see `NoiseBudget::key_switch`; a packed
+/// entry goes through the key once, so each slot only picks up the noise of one block.
+/// Fails with `NoiseTooLarge`, leaving the CRS untouched, when honest proofs flooded
+/// up to 2^-flooding_bits would no longer decode.
+pub fn rotate_verifier_key<R: RngCore + CryptoRng>(
+    crs: &mut CommonReferenceString,
+    vrs: &[FE],
+    rng: &mut R,
+) -> Result<Vec<FE>, ParamsError> {
+    let noise = crs.noise;
+    crs.noise = noise.key_switch(&crs.pk, 1);
+    if !sap_proofs_decode(crs, crs.zeros.len()) {
+        crs.noise = noise;
+        return Err(ParamsError::NoiseTooLarge);
+    }
+
+    let (ksk, new) = rotation_key(&crs.pk, crs.packing, vrs, rng);
+    let switch = |v: &GadgetVector| v.map_encodings(|c| ksk.switch_packed(c));
+    crs.delta_t_s_2 = switch(&crs.delta_t_s_2);
+    crs.beta_t_sk = switch(&crs.beta_t_sk);
+    crs.delta_si = switch(&crs.delta_si);
+    crs.delta_si_t_sk = switch(&crs.delta_si_t_sk);
+    crs.delta_wi_beta_vi = switch(&crs.delta_wi_beta_vi);
+    crs.zeros = ksk.switch_vector(&crs.zeros);
+
+    Ok(flatten(&new))
+}
+
+/// Same as `rotate_verifier_key` for the QAP variant.
+pub fn rotate_qap_verifier_key<R: RngCore + CryptoRng>(
+    crs: &mut QapCommonReferenceString,
+    vrs: &[FE],
+    rng: &mut R,
+) -> Result<Vec<FE>, ParamsError> {
+    let noise = crs.noise;
+    crs.noise = noise.key_switch(&crs.pk, 1);
+    if !qap_proofs_decode(crs, crs.zeros.len()) {
+        crs.noise = noise;
+        return Err(ParamsError::NoiseTooLarge);
+    }
+
+    let (ksk, new) = rotation_key(&crs.pk, crs.packing, vrs, rng);
+    let switch = |v: &GadgetVector| v.map_encodings(|c| ksk.switch_packed(c));
+    crs.scalars = switch(&crs.scalars);
+    crs.si = switch(&crs.si);
+    crs.si_t_delta = switch(&crs.si_t_delta);
+    crs.witness_terms = switch(&crs.witness_terms);
+    crs.zeros = ksk.switch_vector(&crs.zeros);
+
+    Ok(flatten(&new))
+}
+
+/// Same as `rotate_verifier_key` for the SSP variant.
+pub fn rotate_ssp_verifier_key<R: RngCore + CryptoRng>(
+    crs: &mut SspCommonReferenceString,
+    vrs: &[FE],
+    rng: &mut R,
+) -> Result<Vec<FE>, ParamsError> {
+    let noise = crs.noise;
+    crs.noise = noise.key_switch(&crs.pk, 1);
+    if !ssp_proofs_decode(crs, crs.zeros.len()) {
+        crs.noise = noise;
+        return Err(ParamsError::NoiseTooLarge);
+    }
+
+    let (ksk, new) = rotation_key(&crs.pk, crs.packing, vrs, rng);
+    let switch = |v: &GadgetVector| v.map_encodings(|c| ksk.switch_packed(c));
+    crs.scalars = switch(&crs.scalars);
+    crs.si = switch(&crs.si);
+    crs.vi = switch(&crs.vi);
+    crs.beta_vi = switch(&crs.beta_vi);
+    crs.zeros = ksk.switch_vector(&crs.zeros);
+
+    Ok(flatten(&new))
 }
 
 pub fn pow(s: &FEp, deg: usize) -> FEp {
@@ -78,9 +425,10 @@ pub fn pow(s: &FEp, deg: usize) -> FEp {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::test_ex::{new_test_first_constraint, new_test_r1cs, new_test_second_constraint};
+    use crate::test_ex::{new_test_first_constraint, new_test_r1cs, new_test_r1cs_assignment, new_test_second_constraint, TEST_FLOODING_BITS};
 
     use super::*;
+    use crate::{lwe::LWE, noise::centered, prover::prove, ssp::{BooleanCircuit, Gate, GateOp}};
    
     #[test]
     fn test_setup() {
@@ -89,6 +437,158 @@ pub mod tests {
 
         let sap = SAP::r1cs_to_sap(r1cs.clone());
 
-        setup(&sap);
+        setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut rand::thread_rng()).unwrap();
+    }
+
+    #[test]
+    fn rejects_parameters_too_noisy_for_the_proof() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        // a fresh encoding still decodes, a proof summing a few hundred of them does not
+        let noisy = LweParams::new(5, P_MODULUS, Q_MODULUS, 2f64.powi(-40)).unwrap();
+        assert!(NoiseBudget::fresh(&noisy).is_decodable(&noisy));
+
+        assert_eq!(setup(&sap, &noisy, TEST_FLOODING_BITS, &mut seeded_rng(9)).err(), Some(ParamsError::NoiseTooLarge));
+    }
+
+    #[test]
+    fn keeps_the_requested_flooding_parameter() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (_, crs, _) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(10)).unwrap();
+        assert_eq!(crs.flooding_bits, TEST_FLOODING_BITS);
+        assert_eq!(crs.zeros.len(), zero_samples(&crs.pk, TEST_FLOODING_BITS));
+
+        // the toy noise budget cannot flood a proof to 2^-40
+        assert_eq!(setup(&sap, &LweParams::toy(), 40, &mut seeded_rng(10)).err(), Some(ParamsError::NoiseTooLarge));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn presets_cannot_flood_to_40_bits() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        for params in [LweParams::bits_80(), LweParams::bits_128()] {
+            assert_eq!(setup(&sap, &params, 40, &mut seeded_rng(11)).err(), Some(ParamsError::NoiseTooLarge));
+        }
+    }
+
+    #[test]
+    fn setup_is_reproducible_with_seeded_rng() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+
+        let (vrs1, crs1, td1) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(2024)).unwrap();
+        let (vrs2, crs2, td2) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(2024)).unwrap();
+
+        assert_eq!(vrs1, vrs2);
+        assert_eq!(td1, td2);
+        assert_eq!(crs1.delta_t_s_2, crs2.delta_t_s_2);
+        assert_eq!(crs1.delta_si, crs2.delta_si);
+        assert_eq!(crs1.delta_si_t_sk, crs2.delta_si_t_sk);
+    }
+
+    #[test]
+    fn crs_encodings_decode_to_trapdoor_values() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, crs, td) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(7)).unwrap();
+        let (beta, delta, s) = (&td[0], &td[1], &td[2]);
+        let lwe = LWE::new(crs.pk);
+        let t_s = sap.target.evaluate(s);
+
+        assert_eq!(crs.delta_si.len(), sap.target.degree() + 1);
+        assert_eq!(crs.delta_wi_beta_vi.len(), sap.u_polynomials.len() - sap.num_instance_variables);
+        for i in 0..crs.delta_si.len() {
+            assert_eq!(lwe.decode(&vrs, &crs.delta_si.get(i)), Ok(delta * pow(s, i)));
+        }
+        assert_eq!(lwe.decode(&vrs, &crs.beta_t_sk.get(0)), Ok(beta * &t_s));
+        assert_eq!(lwe.decode(&vrs, &crs.delta_t_s_2.get(0)), Ok(delta * &t_s * &t_s));
+    }
+
+    #[test]
+    fn packing_shrinks_the_crs_and_grows_the_proof() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (_, plain, _) = setup_with_packing(&sap, &LweParams::toy(), 1, TEST_FLOODING_BITS, &mut seeded_rng(8)).unwrap();
+        let (vrs, packed, _) = setup_with_packing(&sap, &LweParams::toy(), 4, TEST_FLOODING_BITS, &mut seeded_rng(8)).unwrap();
+
+        let size = |v: &PackedVector| v.encodings.iter().map(|e| e.dimension() + e.slots()).sum::<usize>();
+        assert!(size(&packed.delta_si.powers) < size(&plain.delta_si.powers));
+        assert_eq!(packed.delta_si.len(), plain.delta_si.len());
+        assert_eq!(vrs.len(), packed.dimension());
+
+        let (u, w) = new_test_r1cs_assignment();
+        let plain_proof = prove(&plain, u.clone(), w.clone(), &mut seeded_rng(9)).unwrap();
+        let packed_proof = prove(&packed, u, w, &mut seeded_rng(9)).unwrap();
+        assert_eq!(plain_proof.a.dimension(), plain.pk.n());
+        assert_eq!(packed_proof.a.dimension(), 4 * plain_proof.a.dimension());
+        assert_eq!(packed_proof.b.dimension(), 4 * plain_proof.b.dimension());
+    }
+
+    // key switching adds about 8 bits of noise, the toy noise leaves too little room for flooding after it
+    pub fn quiet_params() -> LweParams {
+        LweParams::new(5, P_MODULUS, Q_MODULUS, 2f64.powi(-64)).unwrap()
+    }
+
+    // largest lambda honest proofs keep under `quiet_params` after one key rotation
+    pub const ROTATION_FLOODING_BITS: u32 = 8;
+
+    #[test]
+    fn rotated_crs_decodes_under_new_key_only() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, mut crs, td) = setup(&sap, &quiet_params(), ROTATION_FLOODING_BITS, &mut seeded_rng(10)).unwrap();
+        let new_vrs = rotate_verifier_key(&mut crs, &vrs, &mut seeded_rng(11)).unwrap();
+        let (delta, s) = (&td[1], &td[2]);
+
+        assert_ne!(new_vrs, vrs);
+        assert_eq!(crs.noise, NoiseBudget::fresh(&crs.pk).key_switch(&crs.pk, 1));
+        let lwe = LWE::new(crs.pk);
+        for i in 0..crs.delta_si.len() {
+            assert_eq!(lwe.decode_with_budget(&new_vrs, &crs.delta_si.get(i), &crs.noise), Ok(delta * pow(s, i)));
+            assert!(lwe.decode_with_budget(&vrs, &crs.delta_si.get(i), &crs.noise).is_err());
+        }
+    }
+
+    #[test]
+    fn rotated_noise_stays_within_the_bound() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, mut crs, _) = setup_with_packing(&sap, &quiet_params(), 4, ROTATION_FLOODING_BITS, &mut seeded_rng(18)).unwrap();
+        let new_vrs = rotate_verifier_key(&mut crs, &vrs, &mut seeded_rng(19)).unwrap();
+
+        // the phase of a delta * s^i entry is its message plus p * e, message < p
+        let noise = (0..crs.delta_si.len())
+            .map(|i| centered(&crs.delta_si.get(i).phase(&new_vrs)).unsigned_abs())
+            .max()
+            .unwrap();
+        assert!(noise <= crs.noise.bound());
+        assert!(crs.noise.bound() < NoiseBudget::fresh(&crs.pk).key_switch(&crs.pk, crs.packing).bound());
+    }
+
+    #[test]
+    fn rotation_keeps_the_crs_when_proofs_would_not_decode() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, mut crs, _) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(12)).unwrap();
+        let (delta_si, flooding_bits) = (crs.delta_si.clone(), crs.flooding_bits);
+
+        assert_eq!(rotate_verifier_key(&mut crs, &vrs, &mut seeded_rng(13)), Err(ParamsError::NoiseTooLarge));
+        assert_eq!(crs.delta_si, delta_si);
+        assert_eq!((crs.noise, crs.flooding_bits), (NoiseBudget::fresh(&crs.pk), flooding_bits));
+    }
+
+    #[test]
+    fn rotates_qap_and_ssp_crs() {
+        let lwe = LWE::new(quiet_params());
+
+        let qap = QAP::r1cs_to_qap(new_test_r1cs());
+        let (vrs, mut crs, td) = setup_qap(&qap, &quiet_params(), ROTATION_FLOODING_BITS, &mut seeded_rng(14)).unwrap();
+        let new_vrs = rotate_qap_verifier_key(&mut crs, &vrs, &mut seeded_rng(15)).unwrap();
+        for (c, m) in [(&crs.scalars.get(2), &td[2]), (&crs.si.get(1), &td[3])] {
+            assert_eq!(lwe.decode_with_budget(&new_vrs, c, &crs.noise), Ok(m.clone()));
+            assert!(lwe.decode_with_budget(&vrs, c, &crs.noise).is_err());
+        }
+
+        let circuit = BooleanCircuit::new(3, 1, vec![Gate { op: GateOp::And, left: 1, right: 2, output: 0 }]);
+        let ssp = SSP::circuit_to_ssp(&circuit);
+        let (vrs, mut crs, td) = setup_ssp(&ssp, &quiet_params(), ROTATION_FLOODING_BITS, &mut seeded_rng(16)).unwrap();
+        let new_vrs = rotate_ssp_verifier_key(&mut crs, &vrs, &mut seeded_rng(17)).unwrap();
+        let t_s = ssp.target.evaluate(&td[1]);
+        for (c, m) in [(&crs.scalars.get(1), &td[0] * &t_s), (&crs.si.get(1), td[1].clone())] {
+            assert_eq!(lwe.decode_with_budget(&new_vrs, c, &crs.noise), Ok(m));
+            assert!(lwe.decode_with_budget(&vrs, c, &crs.noise).is_err());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    common::{Fp, FEp},
+    sap::{coset_evaluations, coset_quotient, combine, domain, lagrange_basis, QuotientError, SparseEvaluations},
+};
+use lambdaworks_math::polynomial::Polynomial;
+
+/// Fan-in 2 boolean gates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GateOp {
+    And,
+    Or,
+    Xor,
+    Nand,
+    Nor,
+    Xnor,
+}
+
+impl GateOp {
+    pub fn apply(&self, a: bool, b: bool) -> bool {
+        match self {
+            GateOp::And => a & b,
+            GateOp::Or => a | b,
+            GateOp::Xor => a ^ b,
+            GateOp::Nand => !(a & b),
+            GateOp::Nor => !(a | b),
+            GateOp::Xnor => !(a ^ b),
+        }
+    }
+
+    /// (x, y, z, k) such that for bits a, b, c: x a + y b + z c + k is -1 or 1
+    /// exactly when c = op(a, b).
+    fn span_constraint(&self) -> (i64, i64, i64, i64) {
+        match self {
+            GateOp::And => (-2, -2, 4, 1),
+            GateOp::Or => (2, 2, -4, 1),
+            GateOp::Xor => (1, 1, 1, -1),
+            GateOp::Nand => (-2, -2, -4, 5),
+            GateOp::Nor => (2, 2, 4, -3),
+            GateOp::Xnor => (1, 1, -1, 0),
+        }
+    }
+}
+
+/// output = op(left, right), all three are wire indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gate {
+    pub op: GateOp,
+    pub left: usize,
+    pub right: usize,
+    pub output: usize,
+}
+
+/// Boolean circuit over `num_wires` wires, the first `num_public` are the public
+/// inputs and outputs of the statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BooleanCircuit {
+    pub num_wires: usize,
+    pub num_public: usize,
+    pub gates: Vec<Gate>,
+}
+
+impl BooleanCircuit {
+    pub fn new(num_wires: usize, num_public: usize, gates: Vec<Gate>) -> Self {
+        assert!(num_public <= num_wires, "public wires should be wires");
+        assert!(
+            gates.iter().all(|g| g.left < num_wires && g.right < num_wires && g.output < num_wires),
+            "gates should connect existing wires"
+        );
+        BooleanCircuit { num_wires, num_public, gates }
+    }
+
+    /// Sets the output of every gate in order, the wires no gate writes are inputs.
+    pub fn propagate(&self, wires: &mut [bool]) {
+        for g in &self.gates {
+            wires[g.output] = g.op.apply(wires[g.left], wires[g.right]);
+        }
+    }
+
+    pub fn is_satisfied(&self, wires: &[bool]) -> bool {
+        self.gates.iter().all(|g| wires[g.output] == g.op.apply(wires[g.left], wires[g.right]))
+    }
+}
+
+/// SSP of a boolean circuit: v(x) = v_0(x) + sum_i a_i v_i(x) with (v(x)^2 - 1) = h(x) t(x).
+/// Row i < num_wires is the bit constraint 2 a_i - 1, the next rows are one constraint per
+/// gate, the padding up to N rows is the constant 1. v_0 is the constant column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SquareSpanProgram {
+    /// Number of public wires plus the leading `1`
+    pub num_instance_variables: usize,
+    pub num_witness_variables: usize,
+    pub num_constraints: usize,
+    pub v_polynomials: Vec<SparseEvaluations>,
+    pub target: Polynomial<FEp>,
+}
+
+impl SquareSpanProgram {
+    pub fn circuit_to_ssp(circuit: &BooleanCircuit) -> Self {
+        let num_wires = circuit.num_wires;
+        let num_constraints = num_wires + circuit.gates.len();
+        let domain_size = num_constraints.next_power_of_two();
+
+        let mut v: Vec<Vec<(usize, FEp)>> = vec![Vec::new(); num_wires + 1];
+        for i in 0..num_wires {
+            v[0].push((i, -FEp::one()));
+            v[i + 1].push((i, FEp::from(2)));
+        }
+        for (j, g) in circuit.gates.iter().enumerate() {
+            let row = num_wires + j;
+            let (x, y, z, k) = g.op.span_constraint();
+            // a gate may use the same wire twice, its coefficients are summed
+            let mut entries: Vec<(usize, i64)> = Vec::with_capacity(4);
+            for (wire, coeff) in [(g.left + 1, x), (g.right + 1, y), (g.output + 1, z), (0, k)] {
+                match entries.iter_mut().find(|(w, _)| *w == wire) {
+                    Some((_, c)) => *c += coeff,
+                    None => entries.push((wire, coeff)),
+                }
+            }
+            for (wire, coeff) in entries.into_iter().filter(|(_, c)| *c != 0) {
+                v[wire].push((row, fep_from_i64(coeff)));
+            }
+        }
+        for row in num_constraints..domain_size {
+            v[0].push((row, FEp::one()));
+        }
+
+        let t = Polynomial::new_monomial(FEp::one(), domain_size) - Polynomial::new_monomial(FEp::one(), 0);
+
+        SquareSpanProgram {
+            num_instance_variables: circuit.num_public + 1,
+            num_witness_variables: num_wires - circuit.num_public,
+            num_constraints,
+            v_polynomials: v.into_iter().map(SparseEvaluations::new).collect(),
+            target: t,
+        }
+    }
+
+    /// Size N of the evaluation domain, t(x) = x^N - 1.
+    pub fn domain_size(&self) -> usize {
+        self.target.degree()
+    }
+
+    pub fn domain(&self) -> Vec<FEp> {
+        domain(self.domain_size())
+    }
+
+    /// L_j(x) for every point w^j of the domain, see `sap::lagrange_basis`.
+    pub fn lagrange_basis_at(&self, x: &FEp) -> Vec<FEp> {
+        lagrange_basis(self.domain_size(), x)
+    }
+
+    /// v(x) in coefficient form, `cs` are the wire values with the constant 1 implicit.
+    pub fn v_polinomial(&self, cs: &[FEp]) -> Polynomial<FEp> {
+        let v_evals = combine(&self.v_polynomials, cs, self.domain_size());
+        Polynomial::interpolate_fft::<Fp>(&v_evals).expect("domain should fit in the two-adic subgroup")
+    }
+
+    /// h(x) = (v(x)^2 - 1) / t(x) through a coset FFT, see `sap::coset_quotient`.
+    pub fn h_polinomial(&self, cs: &[FEp]) -> Result<Polynomial<FEp>, QuotientError> {
+        let n = self.domain_size();
+        let p_evals = coset_evaluations(&self.v_polinomial(cs), n).iter().map(|v| v * v - FEp::one()).collect();
+        coset_quotient(p_evals, n)
+    }
+}
+
+/// Wire values as elements of Z_p.
+pub fn wires_to_fep(wires: &[bool]) -> Vec<FEp> {
+    wires.iter().map(|b| FEp::from(*b as u64)).collect()
+}
+
+fn fep_from_i64(x: i64) -> FEp {
+    let abs = FEp::from(x.unsigned_abs());
+    if x < 0 { -abs } else { abs }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // out = (a AND b) XOR c, a and out public
+    fn circuit() -> BooleanCircuit {
+        let gates = vec![
+            Gate { op: GateOp::And, left: 0, right: 2, output: 4 },
+            Gate { op: GateOp::Xor, left: 4, right: 3, output: 1 },
+        ];
+        BooleanCircuit::new(5, 2, gates)
+    }
+
+    #[test]
+    fn span_constraints_select_gate_outputs() {
+        for op in [GateOp::And, GateOp::Or, GateOp::Xor, GateOp::Nand, GateOp::Nor, GateOp::Xnor] {
+            let (x, y, z, k) = op.span_constraint();
+            for bits in 0..8 {
+                let (a, b, c) = (bits & 1 == 1, bits & 2 == 2, bits & 4 == 4);
+                let value = x * a as i64 + y * b as i64 + z * c as i64 + k;
+                assert_eq!(value.abs() == 1, c == op.apply(a, b), "{op:?} on {a} {b} {c}");
+            }
+        }
+    }
+
+    #[test]
+    fn ssp_accepts_exactly_the_circuit_evaluations() {
+        let circuit = circuit();
+        let ssp = SquareSpanProgram::circuit_to_ssp(&circuit);
+
+        for inputs in 0..8 {
+            let mut wires = vec![inputs & 1 == 1, false, inputs & 2 == 2, inputs & 4 == 4, false];
+            circuit.propagate(&mut wires);
+            assert!(circuit.is_satisfied(&wires));
+            let h = ssp.h_polinomial(&wires_to_fep(&wires)).unwrap();
+            assert!(h.coeff_len() < ssp.domain_size());
+
+            wires[1] = !wires[1];
+            assert_eq!(ssp.h_polinomial(&wires_to_fep(&wires)), Err(QuotientError::NonZeroRemainder));
+        }
+    }
+
+    #[test]
+    fn ssp_rejects_non_boolean_wires() {
+        let circuit = circuit();
+        let ssp = SquareSpanProgram::circuit_to_ssp(&circuit);
+        let mut wires = vec![true, false, true, true, false];
+        circuit.propagate(&mut wires);
+
+        let mut cs = wires_to_fep(&wires);
+        cs[3] = FEp::from(2);
+        assert_eq!(ssp.h_polinomial(&cs), Err(QuotientError::NonZeroRemainder));
+        assert_eq!((ssp.num_instance_variables, ssp.num_witness_variables), (3, 3));
+    }
+}
@@ -1,9 +1,15 @@
 use crate::{common::*, 
-    r1cs::{Constraint, R1CS}, 
-    lwe::*};
+    r1cs::{Constraint, R1CS},
+    sap::SquareArithmeticProgram,
+    lwe::*,
+    params::LweParams};
 use lambdaworks_math::polynomial::Polynomial;
 use std::convert::From;
 
+/// Flooding parameter lambda of the tests and examples, within the noise budget the
+/// toy parameters leave for the test circuits.
+pub const TEST_FLOODING_BITS: u32 = 12;
+
 pub fn new_test_r1cs() -> R1CS {
     let constraints = vec![new_test_first_constraint(), new_test_second_constraint()];
     R1CS::new(constraints, 4, 1).unwrap()
@@ -73,6 +79,26 @@ pub fn new_test_second_constraint() -> Constraint {
     }
 }
 
+/// Public input and R1CS witness of `new_test_r1cs`, the layout `prove` expects:
+/// u = (c1..c4), w = (c5, c6).
+pub fn new_test_r1cs_assignment() -> (Vec<FEp>, Vec<FEp>) {
+    let r1cs = new_test_r1cs();
+    let full: Vec<FEp> = [3u64, 5, 4, 2, 8, 64].iter().map(|&x| FEp::from(x)).collect();
+    let (u, w) = full.split_at(r1cs.num_instance_variables() - 1);
+
+    (u.to_vec(), w.to_vec())
+}
+
+/// Same public input with the SAP witness: w followed by the auxiliary SAP variables
+/// (a_i - b_i)^2 per constraint and (x_i - 1)^2 per input, see `extend_witness`.
+pub fn new_test_sap_assignment() -> (Vec<FEp>, Vec<FEp>) {
+    let sap = SquareArithmeticProgram::r1cs_to_sap(new_test_r1cs());
+    let (u, w) = new_test_r1cs_assignment();
+    let w = sap.extend_witness(&[vec![FEp::from(1)], u.clone(), w].concat()).unwrap();
+
+    (u, w)
+}
+
 pub fn gen_sap_constraints() {
     
     let m: u64 = 7; 
@@ -91,16 +117,18 @@ pub fn gen_sap_constraints() {
         t_x = t_x * Polynomial::new(&[-r, FEp::from(1)]);
     }
 
+    let mut rng = rand::thread_rng();
+
     // generate a_i
     // a_i[0] = 1
-    let a: Vec<FEp> = (1..=m).map(|_| sample_fr_elem_zp()).collect();
+    let a: Vec<FEp> = (1..=m).map(|_| sample_fr_elem_zp(&mut rng)).collect();
     println!("len vector a_m = {}", a.len());
 
     // v_i(x)
     let mut v_x: Vec<Polynomial<FEp>> = Vec::new();
     for _ in 0..=m {
         let coeffs: Vec<FEp> = (0..number_of_constraints)
-            .map(|_| sample_fr_elem_zp()) 
+            .map(|_| sample_fr_elem_zp(&mut rng)) 
             .collect();
         v_x.push(Polynomial::new(&coeffs));
     }
@@ -138,10 +166,10 @@ pub fn gen_sap_constraints() {
 
 pub fn check_lwe() {
 
-    // let pk =  PK{n: 5, p: 7, q: 218, alfa: 0.29};
-    let lwe: LWE = LWE::new(5, 2013265921, 18446744069414584321, 0.000000000000001);
+    let lwe: LWE = LWE::new(LweParams::toy());
 
-    let s = lwe.key_gen();
+    let mut rng = rand::thread_rng();
+    let s = lwe.key_gen(&mut rng);
     
     println!("secret vector:\n");
     for i in &s {
@@ -152,24 +180,20 @@ pub fn check_lwe() {
     }
 
 
-    let m = sample_fr_elem_zp();
-    let (c0, c1) = lwe.encode(m.clone(), &s);
-
-    let mut c0_test = c0.clone();
+    let m = sample_fr_elem_zp(&mut rng);
+    let c = lwe.encode(m.clone(), &s, &mut rng);
 
-    for i in 0..c0.len() {
-        c0_test[i] = -(c0[i].clone());
-    }
-
-    let decode_m = lwe.decode(&s, (&c0_test, &c1));
+    let decode_m = lwe.decode(&s, &c).unwrap();
 
     // let test: FE = FE::from(3);
     // let z = u64::from_str_radix(&test.representative().to_hex(), 16).unwrap();
     // println!("z = {}", test.representative().to_hex());
 
-    println!("encode message = {}", c1);
+    println!("encode message = {}", c.c1);
     println!("m = {}", m);
     println!("decode m = {}", decode_m);
+
+    assert_eq!(m, decode_m);
 }
 
 #[cfg(test)]
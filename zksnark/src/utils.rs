@@ -1,62 +1,201 @@
-// use qfall_math::integer_mod_q::ModulusPolynomialRingZq;
-use qfall_math::{
-    error::MathError,
-    integer_mod_q::{Modulus, ModulusPolynomialRingZq, PolyOverZq, PolynomialRingZq, Zq},
-    traits::SetCoefficient,
+use lambdaworks_math::{
+    field::{element::FieldElement, traits::IsField},
+    polynomial::Polynomial,
 };
 use std::fmt::Display;
 
-pub fn new_anticyclic(
-    n: impl TryInto<i64> + Display,
-    q: impl Into<Modulus>,
-) -> Result<ModulusPolynomialRingZq, MathError> {
-    let mut poly = PolyOverZq::from((1, q));
-    poly.set_coeff(n, 1)?;
-    Ok(ModulusPolynomialRingZq::from(&poly))
-}
+// Polynomial toolkit, generic over the lambdaworks fields of `common.rs` (FEp and FE alike).
+type Poly<F> = Polynomial<FieldElement<F>>;
 
-// создает моном вида X^degree
-pub fn new_monomial(z: Zq, degre: u64) -> PolynomialRingZq {
-    // let mut coefficients = vec![Z::ZERO; degree];
-    // coefficients.push(coefficient);
-    // Self::new(&coefficients)
-    todo!()
+/// Z(x) = prod_i (x - x_i), the constant 1 for an empty domain.
+pub fn vanishing_polynomial<F: IsField>(xs: &[FieldElement<F>]) -> Poly<F> {
+    if xs.is_empty() {
+        return Polynomial::new(&[FieldElement::one()]);
+    }
+    SubproductTree::new(xs).root().clone()
 }
 
-pub fn new(z: Zq, degre: u64) -> PolynomialRingZq {
-    todo!()
+/// w_i = 1 / prod_(j != i) (x_i - x_j), inverted all at once with a single field inversion.
+pub fn barycentric_weights<F: IsField>(xs: &[FieldElement<F>]) -> Result<Vec<FieldElement<F>>, InterpolateError> {
+    let mut weights: Vec<FieldElement<F>> = xs
+        .iter()
+        .enumerate()
+        .map(|(i, xi)| {
+            xs.iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold(FieldElement::one(), |acc, (_, xj)| acc * (xi - xj))
+        })
+        .collect();
+
+    // a repeated point makes one of the products zero
+    FieldElement::inplace_batch_inverse(&mut weights).map_err(|_| InterpolateError::NonUniqueXs)?;
+    Ok(weights)
 }
 
-pub fn interpolate(xs: &Vec<Zq>, ys: &Vec<Zq>) -> Result<PolynomialRingZq, InterpolateError> {
+/// Lagrange interpolation in O(n^2): P = sum_i y_i w_i Z(x) / (x - x_i).
+pub fn interpolate<F: IsField>(xs: &[FieldElement<F>], ys: &[FieldElement<F>]) -> Result<Poly<F>, InterpolateError> {
     if xs.len() != ys.len() {
         return Err(InterpolateError::UnequalLengths(xs.len(), ys.len()));
     }
     if xs.is_empty() {
-        return Ok(Z::ZERO);
+        return Ok(Polynomial::zero());
+    }
+
+    let weights = barycentric_weights(xs)?;
+    let z = vanishing_polynomial(xs);
+
+    let mut coeffs = vec![FieldElement::<F>::zero(); xs.len()];
+    for ((xi, yi), wi) in xs.iter().zip(ys).zip(&weights) {
+        let c = yi * wi;
+        for (acc, q) in coeffs.iter_mut().zip(divide_by_linear(&z, xi)) {
+            *acc = &*acc + &c * q;
+        }
     }
+    Ok(Polynomial::new(&coeffs))
+}
 
-    let mut denominators = Vec::with_capacity(xs.len() * (xs.len() - 1) / 2);
-    let mut indexes = Vec::with_capacity(xs.len());
+/// P(z) from the values ys = P(xs) and the weights of `barycentric_weights`,
+/// P(z) = Z(z) * sum_i w_i y_i / (z - x_i), in O(n) with one inversion.
+pub fn barycentric_evaluate<F: IsField>(
+    xs: &[FieldElement<F>],
+    ys: &[FieldElement<F>],
+    weights: &[FieldElement<F>],
+    z: &FieldElement<F>,
+) -> FieldElement<F> {
+    if let Some(i) = xs.iter().position(|x| x == z) {
+        return ys[i].clone();
+    }
 
-    let mut idx = 0;
+    let mut diffs: Vec<FieldElement<F>> = xs.iter().map(|x| z - x).collect();
+    let z_at = diffs.iter().fold(FieldElement::one(), |acc, d| acc * d);
+    FieldElement::inplace_batch_inverse(&mut diffs).expect("z is not a node");
 
-    for (i, xi) in xs.iter().enumerate().skip(1) {
-        indexes.push(idx);
-        for xj in xs.iter().take(i) {
-            if xi == xj {
-                return Err(InterpolateError::NonUniqueXs);
-            }
-            denominators.push(xi - xj);
-            idx += 1;
+    let sum = diffs
+        .iter()
+        .zip(ys)
+        .zip(weights)
+        .fold(FieldElement::zero(), |acc, ((d, y), w)| acc + d * y * w);
+    z_at * sum
+}
+
+/// Binary tree of the products of (x - x_i): the leaves are the linear factors,
+/// every node is the product of its children and the root is Z(x).
+/// An odd node at the end of a level moves up unchanged.
+pub struct SubproductTree<F: IsField> {
+    points: Vec<FieldElement<F>>,
+    levels: Vec<Vec<Poly<F>>>,
+}
+
+impl<F: IsField> SubproductTree<F> {
+    pub fn new(xs: &[FieldElement<F>]) -> Self {
+        assert!(!xs.is_empty(), "the domain should not be empty");
+
+        let leaves = xs.iter().map(|x| Polynomial::new(&[-x, FieldElement::one()])).collect();
+        let mut levels: Vec<Vec<Poly<F>>> = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => left.mul_with_ref(right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        SubproductTree { points: xs.to_vec(), levels }
+    }
+
+    pub fn points(&self) -> &[FieldElement<F>] {
+        &self.points
+    }
+
+    /// Z(x), the product of all the leaves.
+    pub fn root(&self) -> &Poly<F> {
+        &self.levels.last().unwrap()[0]
+    }
+
+    /// f(x_i) for every point: f is reduced modulo the nodes going down the tree,
+    /// the remainder at leaf i is the constant f(x_i).
+    pub fn evaluate(&self, f: &Poly<F>) -> Vec<FieldElement<F>> {
+        let mut remainders = vec![remainder(f, self.root())];
+
+        for level in self.levels.iter().rev().skip(1) {
+            remainders = level
+                .iter()
+                .enumerate()
+                .map(|(j, node)| remainder(&remainders[j / 2], node))
+                .collect();
+        }
+        remainders.iter().map(|r| r.evaluate(&FieldElement::zero())).collect()
+    }
+
+    /// Interpolation through the tree: c_i = y_i / Z'(x_i), then going up every
+    /// node combines its children as c_left * Z_right + c_right * Z_left.
+    pub fn interpolate(&self, ys: &[FieldElement<F>]) -> Result<Poly<F>, InterpolateError> {
+        if ys.len() != self.points.len() {
+            return Err(InterpolateError::UnequalLengths(self.points.len(), ys.len()));
+        }
+
+        let mut weights = self.evaluate(&derivative(self.root()));
+        FieldElement::inplace_batch_inverse(&mut weights).map_err(|_| InterpolateError::NonUniqueXs)?;
+
+        let mut values: Vec<Poly<F>> = ys
+            .iter()
+            .zip(&weights)
+            .map(|(y, w)| Polynomial::new(&[y * w]))
+            .collect();
+        for level in &self.levels[..self.levels.len() - 1] {
+            values = values
+                .chunks(2)
+                .zip(level.chunks(2))
+                .map(|pair| match pair {
+                    ([v0, v1], [n0, n1]) => v0.mul_with_ref(n1) + v1.mul_with_ref(n0),
+                    ([v], _) => v.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
         }
+        Ok(values.remove(0))
     }
+}
 
-    
+/// Formal derivative sum_i i c_i x^(i-1).
+pub fn derivative<F: IsField>(p: &Poly<F>) -> Poly<F> {
+    let coeffs: Vec<FieldElement<F>> = p
+        .coefficients()
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| FieldElement::<F>::from(i as u64) * c)
+        .collect();
+    Polynomial::new(&coeffs)
+}
 
-    unimplemented!()
+// quotient of p by (x - root) with synthetic division, the remainder p(root) is dropped
+fn divide_by_linear<F: IsField>(p: &Poly<F>, root: &FieldElement<F>) -> Vec<FieldElement<F>> {
+    let coeffs = p.coefficients();
+    let mut quotient = vec![FieldElement::<F>::zero(); coeffs.len().saturating_sub(1)];
+    let mut carry = FieldElement::<F>::zero();
+    for (i, c) in coeffs.iter().enumerate().skip(1).rev() {
+        carry = c + carry * root;
+        quotient[i - 1] = carry.clone();
+    }
+    quotient
 }
 
-#[derive(Debug)]
+fn remainder<F: IsField>(f: &Poly<F>, divisor: &Poly<F>) -> Poly<F> {
+    if f.degree() < divisor.degree() {
+        return f.clone();
+    }
+    f.clone().long_division_with_remainder(divisor).1
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum InterpolateError {
     UnequalLengths(usize, usize),
     NonUniqueXs,
@@ -73,65 +212,73 @@ impl Display for InterpolateError {
     }
 }
 
-#[cfg(feature = "std")]
 impl std::error::Error for InterpolateError {}
 
 #[cfg(test)]
-mod test_new_anticyclic {
-    use crate::utils::new_anticyclic;
-    use qfall_math::{integer::Z, integer_mod_q::PolyOverZq, traits::GetCoefficient};
+mod tests {
+    use super::*;
+    use crate::common::*;
+
+    fn points(n: u64) -> (Vec<FEp>, Vec<FEp>) {
+        let mut rng = seeded_rng(101);
+        let xs = (0..n).map(|i| FEp::from(3 * i + 7)).collect();
+        let ys = (0..n).map(|_| sample_fr_elem_zp(&mut rng)).collect();
+        (xs, ys)
+    }
 
-    /// Ensure that the modulus polynomial has the specified degree.
     #[test]
-    fn degree() {
-        let degrees = [1, 4, 7, 16, 32, 128];
-        for degree in degrees {
-            let poly_mod = new_anticyclic(degree, 7).unwrap();
+    fn interpolation_passes_through_points() {
+        let (xs, ys) = points(9);
+        let p = interpolate(&xs, &ys).unwrap();
 
-            assert_eq!(degree, poly_mod.get_degree());
-        }
+        assert!(p.degree() < 9);
+        assert_eq!(p, Polynomial::interpolate(&xs, &ys).unwrap());
+        assert!(xs.iter().zip(&ys).all(|(x, y)| p.evaluate(x) == *y));
     }
 
-    /// Check whether the method outputs the correct polynomial.
     #[test]
-    fn correct_polynomial() {
-        let degrees = [1, 4, 7, 16, 32, 128];
-        for degree in degrees {
-            let poly_mod = new_anticyclic(degree, 7).unwrap();
-            let poly_zq = PolyOverZq::from(&poly_mod);
-
-            assert_eq!(Z::ONE, poly_zq.get_coeff(degree).unwrap());
-            assert_eq!(Z::ONE, poly_zq.get_coeff(0).unwrap());
-            for i in 1..degree {
-                assert_eq!(Z::ZERO, poly_zq.get_coeff(i).unwrap());
-            }
-        }
+    fn rejects_bad_domains() {
+        let (xs, ys) = points(4);
+        assert_eq!(interpolate(&xs, &ys[..3]), Err(InterpolateError::UnequalLengths(4, 3)));
+
+        let repeated = vec![xs[0].clone(), xs[1].clone(), xs[0].clone()];
+        assert_eq!(interpolate(&repeated, &ys[..3]), Err(InterpolateError::NonUniqueXs));
+        assert_eq!(interpolate::<Fp>(&[], &[]), Ok(Polynomial::zero()));
     }
 
-    /// Ensures that the correct modulus is set as
-    /// the integer modulus of the output modulus polynomial.
     #[test]
-    fn correct_modulus() {
-        let moduli = [7, 10, i64::MAX];
-        for modulus in moduli {
-            let poly_mod = new_anticyclic(2, modulus).unwrap();
+    fn barycentric_matches_polynomial_evaluation() {
+        let (xs, ys) = points(7);
+        let p = interpolate(&xs, &ys).unwrap();
+        let weights = barycentric_weights(&xs).unwrap();
 
-            assert_eq!(Z::from(modulus), poly_mod.get_q());
+        for z in [FEp::from(1000), FEp::from(5), xs[3].clone()] {
+            assert_eq!(barycentric_evaluate(&xs, &ys, &weights, &z), p.evaluate(&z));
         }
     }
 
-    /// Ensures that an invalid degree for the modulus polynomial results in an error.
     #[test]
-    fn invalid_n() {
-        let res = new_anticyclic(-1, 7);
+    fn vanishing_polynomial_vanishes_on_domain() {
+        let (xs, _) = points(6);
+        let z = vanishing_polynomial(&xs);
+
+        assert_eq!(z.degree(), 6);
+        assert!(xs.iter().all(|x| z.evaluate(x) == FEp::from(0)));
+        assert_ne!(z.evaluate(&FEp::from(1)), FEp::from(0));
 
-        assert!(res.is_err());
+        // over Goldilocks as well
+        let z = vanishing_polynomial(&[FE::from(2), FE::from(3)]);
+        assert_eq!(z.coefficients(), &[FE::from(6), -FE::from(5), FE::from(1)]);
     }
 
-    /// Ensures that an invalid modulus for the modulus polynomial results in a panic.
     #[test]
-    #[should_panic]
-    fn invalid_modulus() {
-        let _ = new_anticyclic(2, 0);
+    fn subproduct_tree_evaluates_and_interpolates() {
+        let (xs, ys) = points(11);
+        let tree = SubproductTree::new(&xs);
+        let p = interpolate(&xs, &ys).unwrap();
+
+        assert_eq!(tree.evaluate(&p), ys);
+        assert_eq!(tree.interpolate(&ys).unwrap(), p);
+        assert_eq!(tree.root(), &vanishing_polynomial(&xs));
     }
-}
\ No newline at end of file
+}
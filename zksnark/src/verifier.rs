@@ -1,36 +1,529 @@
-use crate::{common::*, setup::CommonReferenceString};
-use lambdaworks_math::polynomial::Polynomial;
+use crate::{
+    common::*,
+    lwe::LWE,
+    modswitch::{decode_switched, is_switching_modulus, SwitchedEncoding},
+    noise::NoiseBudget,
+    prover::{Proof, QapProof, SspProof, SwitchedProof},
+    sap::evaluate_sum,
+    setup::{CommonReferenceString, QapCommonReferenceString, SspCommonReferenceString},
+};
 
 
-pub fn verify(a_proof: FE, b_proof: FE,  u: Vec<FEp>, crs:CommonReferenceString, beta: FEp, delta: FEp) -> bool {
+/// Designated verifier check, needs the LWE secret `vrs` and the trapdoor `td = [beta, delta, s]`
+/// returned by `setup`. Decodes both proof elements and checks
+/// `A * (A + beta) = delta * (B + delta * w_io(s) + beta * u_io(s))`,
+/// where u_io, w_io are the parts of u(x), w(x) fixed by the public input.
+/// Proof elements are decoded with the largest decodable bound, `prover::proof_noise_budget`
+/// tells whether honest proofs for the circuit stay within it.
+pub fn verify(proof: &Proof, u: Vec<FEp>, crs: &CommonReferenceString, vrs: &Vec<FE>, td: &[FEp]) -> bool {
+    let lwe = LWE::new(crs.pk);
+    let budget = NoiseBudget::max_decodable(&crs.pk);
+    match (
+        lwe.decode_with_budget(vrs, &proof.a, &budget),
+        lwe.decode_with_budget(vrs, &proof.b, &budget),
+    ) {
+        (Ok(a), Ok(b)) => check(a, b, u, crs, td),
+        _ => false,
+    }
+}
+
+/// Same as `verify` for a proof switched to a smaller modulus q', decoded with
+/// the largest bound below q' / 2. Decoding only succeeds for short verifier secrets.
+/// q' comes with the proof, so it is rejected unless `switch_modulus` could have
+/// produced it and both elements use it at the dimension of the CRS.
+pub fn verify_switched(proof: &SwitchedProof, u: Vec<FEp>, crs: &CommonReferenceString, vrs: &[FE], td: &[FEp]) -> bool {
+    let well_formed = |c: &SwitchedEncoding| c.modulus == proof.a.modulus && c.dimension() == crs.dimension();
+    if !is_switching_modulus(&crs.pk, proof.a.modulus) || !well_formed(&proof.a) || !well_formed(&proof.b) {
+        return false;
+    }
+
+    let budget = NoiseBudget::new((proof.a.modulus / 2) as u128 - 1);
+    match (
+        decode_switched(&crs.pk, vrs, &proof.a, &budget),
+        decode_switched(&crs.pk, vrs, &proof.b, &budget),
+    ) {
+        (Ok(a), Ok(b)) => check(a, b, u, crs, td),
+        _ => false,
+    }
+}
+
+fn check(a: FEp, b: FEp, u: Vec<FEp>, crs: &CommonReferenceString, td: &[FEp]) -> bool {
+    let (beta, delta, s) = (&td[0], &td[1], &td[2]);
+    let mid = u.len();
+
+    let full: Vec<FEp> = [vec![FEp::from(1)], u].concat();
+    let basis = crs.sap.lagrange_basis_at(s);
+    let u_s = evaluate_sum(&crs.sap.u_polynomials[..=mid], &full, &basis);
+    let w_s = evaluate_sum(&crs.sap.w_polynomials[..=mid], &full, &basis);
+
+    let w_plus_u = delta * w_s + beta * u_s;
+
+    // check
+    a.clone() * (a + beta) == delta * (b + w_plus_u)
+}
+
+/// Verifier of the QAP variant, `td = [alpha, beta, delta, s]` from `setup_qap`. Checks
+/// `A * B = alpha * beta + sum_io s_i (beta u_i(s) + alpha v_i(s) + w_i(s)) + C * delta`.
+pub fn verify_qap(proof: &QapProof, u: Vec<FEp>, crs: &QapCommonReferenceString, vrs: &[FE], td: &[FEp]) -> bool {
+    let lwe = LWE::new(crs.pk);
+    let budget = NoiseBudget::max_decodable(&crs.pk);
+    let vrs = vrs.to_vec();
+    let (a, b, c) = match (
+        lwe.decode_with_budget(&vrs, &proof.a, &budget),
+        lwe.decode_with_budget(&vrs, &proof.b, &budget),
+        lwe.decode_with_budget(&vrs, &proof.c, &budget),
+    ) {
+        (Ok(a), Ok(b), Ok(c)) => (a, b, c),
+        _ => return false,
+    };
+
+    let (alpha, beta, delta, s) = (&td[0], &td[1], &td[2], &td[3]);
     let mid = u.len();
-    let u_x: Polynomial<FEp> = crs.sap.u_polynomials[0].clone()
-    + crs.sap.u_polynomials[1..mid]
-        .iter()
-        .zip(u.clone())
-        .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-        .reduce(|x, y| x + y)
-        .unwrap();
+    let full: Vec<FEp> = [vec![FEp::from(1)], u].concat();
+    let basis = crs.qap.lagrange_basis_at(s);
+    let io = beta * evaluate_sum(&crs.qap.u_polynomials[..=mid], &full, &basis)
+        + alpha * evaluate_sum(&crs.qap.v_polynomials[..=mid], &full, &basis)
+        + evaluate_sum(&crs.qap.w_polynomials[..=mid], &full, &basis);
 
-    let w_x: Polynomial<FEp> = crs.sap.w_polynomials[0].clone()
-    + crs.sap.w_polynomials[1..mid]
-        .iter()
-        .zip(u.clone())
-        .map(|(v, c)| v.mul_with_ref(&Polynomial::new_monomial(c.clone(), 0)))
-        .reduce(|x, y| x + y)
-        .unwrap();
+    a * b == alpha * beta + io + c * delta
+}
 
-    let mut w_plus_u = FE::from(0);
+/// Verifier of the SSP variant, `td = [beta, s]` from `setup_ssp`. Checks
+/// `B = beta * V` and `(v_0(s) + v_io(s) + V)^2 - 1 = H * t(s)`.
+pub fn verify_ssp(proof: &SspProof, u: Vec<FEp>, crs: &SspCommonReferenceString, vrs: &[FE], td: &[FEp]) -> bool {
+    let lwe = LWE::new(crs.pk);
+    let budget = NoiseBudget::max_decodable(&crs.pk);
+    let vrs = vrs.to_vec();
+    let (h, v, b) = match (
+        lwe.decode_with_budget(&vrs, &proof.h, &budget),
+        lwe.decode_with_budget(&vrs, &proof.v, &budget),
+        lwe.decode_with_budget(&vrs, &proof.b, &budget),
+    ) {
+        (Ok(h), Ok(v), Ok(b)) => (h, v, b),
+        _ => return false,
+    };
 
-    for i in 0..u.len() {
-        w_plus_u += FE::from_hex_unchecked(&u[i].representative().to_hex()) * crs.delta_wi_beta_vi[i]
+    let (beta, s) = (&td[0], &td[1]);
+    let mid = u.len();
+    let full: Vec<FEp> = [vec![FEp::from(1)], u].concat();
+    let basis = crs.ssp.lagrange_basis_at(s);
+    let v_s = evaluate_sum(&crs.ssp.v_polynomials[..=mid], &full, &basis) + &v;
+
+    b == beta * v && v_s.clone() * v_s - FEp::from(1) == h * crs.ssp.target.evaluate(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoding::Encoding,
+        gadget::GadgetVector,
+        modswitch::switching_modulus,
+        params::{LweParams, ParamsError},
+        prover::{flooded_noise_budget, flooded_qap_noise_budget, flooded_ssp_noise_budget, prove, prove_qap, prove_ssp, SspProof, SwitchedProof},
+        qap::QuadraticArithmeticProgram as QAP,
+        secret::SecretDistribution,
+        sap::{AssignmentError, QuotientError, SparseEvaluations, SquareArithmeticProgram as SAP},
+        setup::{rotate_verifier_key, setup, setup_qap, setup_ssp, tests::{quiet_params, ROTATION_FLOODING_BITS}},
+        ssp::{wires_to_fep, BooleanCircuit, Gate, GateOp, SquareSpanProgram as SSP},
+        test_ex::{new_test_r1cs, new_test_r1cs_assignment, new_test_sap_assignment, TEST_FLOODING_BITS},
+    };
+
+    // A = delta * (u(s) + r t(s)), B = delta * w_wit(s) + beta * u_wit(s) + g(r) + delta * t(s) h(s)
+    fn honest_plaintexts(sap: &SAP, u: &[FEp], w: &[FEp], td: &[FEp], r: &FEp) -> (FEp, FEp) {
+        let (beta, delta, s) = (&td[0], &td[1], &td[2]);
+        let full: Vec<FEp> = [vec![FEp::from(1)], u.to_vec(), w.to_vec()].concat();
+        let basis = sap.lagrange_basis_at(s);
+        let eval = |polys: &[SparseEvaluations], from: usize| evaluate_sum(&polys[from..], &full[from..], &basis);
+
+        let t_s = sap.target.evaluate(s);
+        let u_s = eval(&sap.u_polynomials, 0);
+        let h_s = sap.h_polinomial(&full[1..]).unwrap().evaluate(s);
+        let witness = sap.num_instance_variables;
+
+        let a = delta * (&u_s + r * &t_s);
+        let b = delta * eval(&sap.w_polynomials, witness)
+            + beta * eval(&sap.u_polynomials, witness)
+            + r * r * delta * &t_s * &t_s
+            + FEp::from(2) * r * delta * &t_s * &u_s
+            + r * beta * &t_s
+            + delta * &t_s * h_s;
+        (a, b)
     }
 
-    // check
-    if ( a_proof.clone() * (a_proof + FE::from_hex_unchecked(&beta.representative().to_hex())) ==
-    FE::from_hex_unchecked(&delta.representative().to_hex()) * (b_proof + w_plus_u)) {
-        return true
+    // fresh encodings of dimension packing * n under the flattened secret
+    fn encode_proof(crs: &CommonReferenceString, vrs: &Vec<FE>, a: FEp, b: FEp) -> Proof {
+        let pk = &crs.pk;
+        let lwe = LWE::new(LweParams::new(crs.dimension(), pk.p(), pk.q(), pk.alfa()).unwrap());
+        let mut rng = seeded_rng(5);
+        Proof { a: lwe.encode(a, vrs, &mut rng), b: lwe.encode(b, vrs, &mut rng) }
+    }
+
+    // A = alpha + u(s) + r delta, B = beta + v(s) + r' delta,
+    // C = (sum_wit s_i L_i(s) + h(s) t(s)) / delta + r' A + r B - r r' delta
+    fn honest_qap_plaintexts(qap: &QAP, u: &[FEp], w: &[FEp], td: &[FEp], r: &FEp, r_prime: &FEp) -> (FEp, FEp, FEp) {
+        let (alpha, beta, delta, s) = (&td[0], &td[1], &td[2], &td[3]);
+        let cs: Vec<FEp> = [u, w].concat();
+        let full: Vec<FEp> = [vec![FEp::from(1)], cs.clone()].concat();
+        let basis = qap.lagrange_basis_at(s);
+        let witness = qap.num_instance_variables;
+        let eval = |polys: &[SparseEvaluations]| evaluate_sum(&polys[witness..], &full[witness..], &basis);
+
+        let a = alpha + qap.u_polinomial(&cs).evaluate(s) + r * delta;
+        let b = beta + qap.v_polinomial(&cs).evaluate(s) + r_prime * delta;
+        let h_t = qap.h_polinomial(&cs).unwrap().evaluate(s) * qap.target.evaluate(s);
+        let c = (beta * eval(&qap.u_polynomials) + alpha * eval(&qap.v_polynomials) + eval(&qap.w_polynomials) + h_t)
+            * delta.inv().unwrap()
+            + r_prime * &a
+            + r * &b
+            - r * r_prime * delta;
+        (a, b, c)
+    }
+
+    fn encode_qap_proof(dimension: usize, pk: &LweParams, vrs: &Vec<FE>, (a, b, c): (FEp, FEp, FEp)) -> QapProof {
+        let lwe = LWE::new(LweParams::new(dimension, pk.p(), pk.q(), pk.alfa()).unwrap());
+        let mut rng = seeded_rng(15);
+        QapProof { a: lwe.encode(a, vrs, &mut rng), b: lwe.encode(b, vrs, &mut rng), c: lwe.encode(c, vrs, &mut rng) }
+    }
+
+    #[test]
+    fn qap_verifier_accepts_honest_plaintexts_only() {
+        let qap = QAP::r1cs_to_qap(new_test_r1cs());
+        let (vrs, crs, td) = setup_qap(&qap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(16)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let plaintexts = honest_qap_plaintexts(&qap, &u, &w, &td, &FEp::from(17), &FEp::from(23));
+        let proof = encode_qap_proof(crs.dimension(), &crs.pk, &vrs, plaintexts);
+        assert!(verify_qap(&proof, u.clone(), &crs, &vrs, &td));
+
+        let mut other = u;
+        other[0] += FEp::from(1);
+        assert!(!verify_qap(&proof, other, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn qap_prover_rejects_bad_assignments() {
+        let qap = QAP::r1cs_to_qap(new_test_r1cs());
+        let (_vrs, crs, _td) = setup_qap(&qap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(44)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let short = prove_qap(&crs, u.clone(), w[1..].to_vec(), &mut seeded_rng(45)).err();
+        assert_eq!(short, Some(AssignmentError::WrongLength { expected: u.len() + w.len(), actual: u.len() + w.len() - 1 }));
+
+        let mut bad = w;
+        bad[0] += FEp::from(1);
+        let unsatisfied = prove_qap(&crs, u, bad, &mut seeded_rng(45)).err();
+        assert_eq!(unsatisfied, Some(AssignmentError::Quotient(QuotientError::NonZeroRemainder)));
+    }
+
+    #[test]
+    fn qap_verifier_accepts_honest_proofs() {
+        let qap = QAP::r1cs_to_qap(new_test_r1cs());
+        let (vrs, crs, td) = setup_qap(&qap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(33)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let (a_budget, b_budget, c_budget) = flooded_qap_noise_budget(&crs);
+        assert!([a_budget, b_budget, c_budget].iter().all(|b| b.is_decodable(&crs.pk)));
+        assert_eq!(crs.flooding_bits, TEST_FLOODING_BITS);
+        let proof = prove_qap(&crs, u.clone(), w, &mut seeded_rng(34)).unwrap();
+        assert!(verify_qap(&proof, u.clone(), &crs, &vrs, &td));
+
+        let mut other = u;
+        other[0] += FEp::from(1);
+        assert!(!verify_qap(&proof, other, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn qap_proof_is_larger_but_its_crs_smaller_than_sap() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let qap = QAP::r1cs_to_qap(new_test_r1cs());
+        let (_, sap_crs, _) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(35)).unwrap();
+        let (_, qap_crs, _) = setup_qap(&qap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(35)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        // the QAP skips the squaring trick, half the domain for the same R1CS
+        assert_eq!(2 * qap.target.degree(), sap.target.degree());
+        let sap_messages = [&sap_crs.delta_t_s_2, &sap_crs.beta_t_sk, &sap_crs.delta_si, &sap_crs.delta_si_t_sk, &sap_crs.delta_wi_beta_vi];
+        let qap_messages = [&qap_crs.scalars, &qap_crs.si, &qap_crs.si_t_delta, &qap_crs.witness_terms];
+        let count = |v: &[&GadgetVector]| v.iter().map(|x| x.len()).sum::<usize>();
+        assert!(2 * count(&qap_messages) <= count(&sap_messages));
+
+        let size = |e: &Encoding| e.dimension() + 1;
+        let sap_proof = prove(&sap_crs, u.clone(), w.clone(), &mut seeded_rng(36)).unwrap();
+        let qap_proof = prove_qap(&qap_crs, u, w, &mut seeded_rng(36)).unwrap();
+        assert_eq!(2 * (size(&qap_proof.a) + size(&qap_proof.b) + size(&qap_proof.c)), 3 * (size(&sap_proof.a) + size(&sap_proof.b)));
+    }
+
+    #[test]
+    fn qap_prover_outputs_three_full_encodings() {
+        let qap = QAP::r1cs_to_qap(new_test_r1cs());
+        let (_vrs, crs, _td) = setup_qap(&qap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(18)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let proof = prove_qap(&crs, u, w, &mut seeded_rng(19)).unwrap();
+        for e in [&proof.a, &proof.b, &proof.c] {
+            assert_eq!(e.dimension(), crs.dimension());
+        }
+        assert_ne!(proof.a, proof.b);
+    }
+
+    // out = (a XOR b) AND c with a and out public, wires (a, out, b, c, a XOR b)
+    fn ssp_statement() -> (SSP, Vec<FEp>, Vec<FEp>) {
+        let gates = vec![
+            Gate { op: GateOp::Xor, left: 0, right: 2, output: 4 },
+            Gate { op: GateOp::And, left: 4, right: 3, output: 1 },
+        ];
+        let circuit = BooleanCircuit::new(5, 2, gates);
+        let mut wires = vec![true, false, false, true, false];
+        circuit.propagate(&mut wires);
+
+        let wires = wires_to_fep(&wires);
+        (SSP::circuit_to_ssp(&circuit), wires[..2].to_vec(), wires[2..].to_vec())
+    }
+
+    #[test]
+    fn ssp_verifier_accepts_honest_plaintexts_only() {
+        let (ssp, u, w) = ssp_statement();
+        let (vrs, crs, td) = setup_ssp(&ssp, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(20)).unwrap();
+        let (beta, s) = (&td[0], &td[1]);
+
+        // H = h(s) + 2 d v(s) + d^2 t(s), V = v_wit(s) + d t(s), B = beta V
+        let d = FEp::from(29);
+        let full = [u.clone(), w.clone()].concat();
+        let (t_s, v_full) = (ssp.target.evaluate(s), ssp.v_polinomial(&full).evaluate(s));
+        let basis = ssp.lagrange_basis_at(s);
+        let v_wit = evaluate_sum(&ssp.v_polynomials[ssp.num_instance_variables..], &w, &basis) + &d * &t_s;
+        let h = ssp.h_polinomial(&full).unwrap().evaluate(s) + FEp::from(2) * &d * v_full + &d * &d * &t_s;
+
+        let pk = &crs.pk;
+        let lwe = LWE::new(LweParams::new(crs.dimension(), pk.p(), pk.q(), pk.alfa()).unwrap());
+        let mut rng = seeded_rng(21);
+        let mut encode = |m: FEp| lwe.encode(m, &vrs, &mut rng);
+        let proof = SspProof { h: encode(h.clone()), v: encode(v_wit.clone()), b: encode(beta * &v_wit) };
+        assert!(verify_ssp(&proof, u.clone(), &crs, &vrs, &td));
+
+        let forged = SspProof { h: encode(h), v: encode(v_wit.clone()), b: encode(beta * &v_wit + FEp::from(1)) };
+        assert!(!verify_ssp(&forged, u.clone(), &crs, &vrs, &td));
+
+        let mut other = u;
+        other[1] += FEp::from(1);
+        assert!(!verify_ssp(&proof, other, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn ssp_prover_outputs_full_encodings() {
+        let (ssp, u, w) = ssp_statement();
+        let (_vrs, crs, _td) = setup_ssp(&ssp, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(22)).unwrap();
+
+        let proof = prove_ssp(&crs, u, w, &mut seeded_rng(23)).unwrap();
+        for e in [&proof.h, &proof.v, &proof.b] {
+            assert_eq!(e.dimension(), crs.dimension());
+        }
+    }
+
+    #[test]
+    fn ssp_prover_rejects_bad_wires() {
+        let (ssp, u, w) = ssp_statement();
+        let (_vrs, crs, _td) = setup_ssp(&ssp, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(46)).unwrap();
+
+        let long = prove_ssp(&crs, u.clone(), [w.clone(), vec![FEp::from(0)]].concat(), &mut seeded_rng(47)).err();
+        assert_eq!(long, Some(AssignmentError::WrongLength { expected: u.len() + w.len(), actual: u.len() + w.len() + 1 }));
+
+        let mut bad = w;
+        bad[0] = FEp::from(1) - &bad[0];
+        let unsatisfied = prove_ssp(&crs, u, bad, &mut seeded_rng(47)).err();
+        assert_eq!(unsatisfied, Some(AssignmentError::Quotient(QuotientError::NonZeroRemainder)));
+    }
+
+    #[test]
+    fn ssp_verifier_accepts_honest_proofs() {
+        let (ssp, u, w) = ssp_statement();
+        let (vrs, crs, td) = setup_ssp(&ssp, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(37)).unwrap();
+
+        let (h_budget, v_budget) = flooded_ssp_noise_budget(&crs);
+        assert!(h_budget.is_decodable(&crs.pk) && v_budget.is_decodable(&crs.pk));
+        assert_eq!(crs.flooding_bits, TEST_FLOODING_BITS);
+        let proof = prove_ssp(&crs, u.clone(), w, &mut seeded_rng(38)).unwrap();
+        assert!(verify_ssp(&proof, u.clone(), &crs, &vrs, &td));
+
+        let mut other = u;
+        other[1] += FEp::from(1);
+        assert!(!verify_ssp(&proof, other, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn accepts_honest_proof_plaintexts() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, crs, td) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(3)).unwrap();
+        let (u, w) = new_test_sap_assignment();
+
+        let (a, b) = honest_plaintexts(&sap, &u, &w, &td, &FEp::from(17));
+        assert!(verify(&encode_proof(&crs, &vrs, a, b), u, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn rejects_proof_for_other_public_input() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, crs, td) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(4)).unwrap();
+        let (u, w) = new_test_sap_assignment();
+
+        let (a, b) = honest_plaintexts(&sap, &u, &w, &td, &FEp::from(17));
+        let mut other = u.clone();
+        other[0] += FEp::from(1);
+        assert!(!verify(&encode_proof(&crs, &vrs, a, b), other, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn accepts_switched_proof_under_ternary_secret() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let params = LweParams::toy().with_secret(SecretDistribution::Ternary).unwrap();
+        let (vrs, crs, td) = setup(&sap, &params, TEST_FLOODING_BITS, &mut seeded_rng(9)).unwrap();
+        let (u, w) = new_test_sap_assignment();
+
+        let (a, b) = honest_plaintexts(&sap, &u, &w, &td, &FEp::from(17));
+        let q_prime = switching_modulus(&crs.pk, 56).unwrap();
+        let proof = encode_proof(&crs, &vrs, a, b).switch_modulus(&crs.pk, q_prime).unwrap();
+        assert!(verify_switched(&proof, u, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn switched_honest_proofs_survive_serialization() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let params = LweParams::toy().with_secret(SecretDistribution::Ternary).unwrap();
+        let (vrs, crs, td) = setup(&sap, &params, TEST_FLOODING_BITS, &mut seeded_rng(26)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let q_prime = switching_modulus(&crs.pk, 56).unwrap();
+        let proof = prove(&crs, u.clone(), w, &mut seeded_rng(27)).unwrap().switch_modulus(&crs.pk, q_prime).unwrap();
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), proof.a.byte_len() + proof.b.byte_len());
+
+        let proof = SwitchedProof::from_bytes(&bytes).unwrap();
+        assert!(verify_switched(&proof, u, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn switched_proofs_with_forged_moduli_are_rejected() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let params = LweParams::toy().with_secret(SecretDistribution::Ternary).unwrap();
+        let (vrs, crs, td) = setup(&sap, &params, TEST_FLOODING_BITS, &mut seeded_rng(48)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let q_prime = switching_modulus(&crs.pk, 56).unwrap();
+        let proof = prove(&crs, u.clone(), w, &mut seeded_rng(49)).unwrap().switch_modulus(&crs.pk, q_prime).unwrap();
+        assert!(verify_switched(&proof, u.clone(), &crs, &vrs, &td));
+
+        // entries reduced mod the forged modulus so the proof is still well formed
+        let forge = |c: &SwitchedEncoding, modulus: u64| SwitchedEncoding {
+            modulus,
+            c0: c.c0.iter().map(|x| x % modulus).collect(),
+            c1: c.c1 % modulus,
+        };
+        assert!(is_switching_modulus(&crs.pk, q_prime + P_MODULUS));
+        for modulus in [q_prime + 1, Q_MODULUS, 2 * P_MODULUS + 1, 3] {
+            assert!(!is_switching_modulus(&crs.pk, modulus));
+            let forged = SwitchedProof { a: forge(&proof.a, modulus), b: forge(&proof.b, modulus) };
+            assert!(!verify_switched(&forged, u.clone(), &crs, &vrs, &td), "q' = {}", modulus);
+        }
+
+        let mixed = SwitchedProof { a: proof.a.clone(), b: forge(&proof.b, q_prime - P_MODULUS) };
+        assert!(!verify_switched(&mixed, u.clone(), &crs, &vrs, &td));
+        let short = SwitchedProof { a: SwitchedEncoding { c0: proof.a.c0[1..].to_vec(), ..proof.a.clone() }, b: proof.b };
+        assert!(!verify_switched(&short, u, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn switching_rejects_uniform_verifier_secrets() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (_, crs, _) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(28)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let q_prime = switching_modulus(&crs.pk, 56).unwrap();
+        let proof = prove(&crs, u, w, &mut seeded_rng(29)).unwrap();
+        assert_eq!(proof.switch_modulus(&crs.pk, q_prime), Err(ParamsError::UniformSecret));
+    }
+
+    #[test]
+    fn prover_outputs_full_encodings() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (_vrs, crs, _td) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(6)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let proof = prove(&crs, u, w, &mut seeded_rng(8)).unwrap();
+        assert_eq!(proof.a.dimension(), crs.dimension());
+        assert_ne!(proof.b, Encoding::zero(crs.dimension()));
+    }
+
+    #[test]
+    fn prover_rejects_bad_assignments() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (_vrs, crs, _td) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(42)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+        let variables = sap.num_instance_variables + sap.num_r1cs_witness_variables;
+
+        let short = prove(&crs, u.clone(), w[1..].to_vec(), &mut seeded_rng(43)).err();
+        assert_eq!(short, Some(AssignmentError::WrongLength { expected: variables, actual: variables - 1 }));
+        let short = prove(&crs, u[1..].to_vec(), w.clone(), &mut seeded_rng(43)).err();
+        assert_eq!(short, Some(AssignmentError::WrongLength { expected: u.len(), actual: u.len() - 1 }));
+
+        let mut bad = w;
+        bad[0] += FEp::from(1);
+        assert!(matches!(prove(&crs, u, bad, &mut seeded_rng(43)), Err(AssignmentError::Unsatisfied(_))));
     }
 
-    false
-}
\ No newline at end of file
+    #[test]
+    fn accepts_honest_proofs() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, crs, td) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(24)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let (a_budget, b_budget) = flooded_noise_budget(&crs);
+        assert!(a_budget.is_decodable(&crs.pk) && b_budget.is_decodable(&crs.pk));
+        assert_eq!(crs.flooding_bits, TEST_FLOODING_BITS);
+        let proof = prove(&crs, u.clone(), w.clone(), &mut seeded_rng(25)).unwrap();
+        assert!(verify(&proof, u.clone(), &crs, &vrs, &td));
+
+        let mut other = u;
+        other[0] += FEp::from(1);
+        assert!(!verify(&proof, other, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn presets_accept_honest_proofs() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (u, w) = new_test_r1cs_assignment();
+
+        for params in [LweParams::bits_80(), LweParams::bits_128()] {
+            let (vrs, crs, td) = setup(&sap, &params, TEST_FLOODING_BITS, &mut seeded_rng(40)).unwrap();
+            let proof = prove(&crs, u.clone(), w.clone(), &mut seeded_rng(41)).unwrap();
+            assert_eq!(proof.a.dimension(), params.n());
+            assert!(verify(&proof, u.clone(), &crs, &vrs, &td));
+        }
+    }
+
+    #[test]
+    fn accepts_proofs_made_after_key_rotation() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, mut crs, td) = setup(&sap, &quiet_params(), ROTATION_FLOODING_BITS, &mut seeded_rng(30)).unwrap();
+        let new_vrs = rotate_verifier_key(&mut crs, &vrs, &mut seeded_rng(31)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let proof = prove(&crs, u.clone(), w, &mut seeded_rng(32)).unwrap();
+        assert!(verify(&proof, u.clone(), &crs, &new_vrs, &td));
+        assert!(!verify(&proof, u, &crs, &vrs, &td));
+    }
+
+    #[test]
+    fn proofs_are_rerandomized() {
+        let sap = SAP::r1cs_to_sap(new_test_r1cs());
+        let (vrs, crs, td) = setup(&sap, &LweParams::toy(), TEST_FLOODING_BITS, &mut seeded_rng(12)).unwrap();
+        let (u, w) = new_test_r1cs_assignment();
+
+        let p1 = prove(&crs, u.clone(), w.clone(), &mut seeded_rng(13)).unwrap();
+        let p2 = prove(&crs, u.clone(), w, &mut seeded_rng(14)).unwrap();
+        assert_ne!(p1.a.c0, p2.a.c0);
+        assert_ne!(p1.b.c0, p2.b.c0);
+        assert!(verify(&p1, u.clone(), &crs, &vrs, &td));
+        assert!(verify(&p2, u, &crs, &vrs, &td));
+    }
+}